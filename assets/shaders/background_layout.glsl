@@ -0,0 +1,37 @@
+﻿// Lays the background image out on the screen, see background.rs. Prepended to every shader that
+// shows the background.
+
+uniform vec2 BackgroundScale;
+uniform vec2 BackgroundOffset;
+uniform bool BackgroundTile;
+uniform vec3 LetterboxColor;
+// The previous background, laid out the same way, fading out until Crossfade reaches 1.
+uniform sampler2D PreviousBackground;
+uniform vec2 PreviousScale;
+uniform vec2 PreviousOffset;
+uniform float Crossfade = 1.0;
+
+vec3 layOut(sampler2D background, vec2 uv)
+{
+    if (!BackgroundTile && (any(lessThan(uv, vec2(0.0))) || any(greaterThan(uv, vec2(1.0)))))
+    {
+        return LetterboxColor;
+    }
+
+    // The texture repeats, which tiles it.
+    return texture(background, uv).rgb;
+}
+
+vec3 sampleBackground(sampler2D background, vec2 screenUv)
+{
+    vec3 color = layOut(background, screenUv * BackgroundScale + BackgroundOffset);
+
+    if (Crossfade < 1.0)
+    {
+        vec3 previous = layOut(PreviousBackground, screenUv * PreviousScale + PreviousOffset);
+
+        color = mix(previous, color, Crossfade);
+    }
+
+    return color;
+}
@@ -0,0 +1,84 @@
+﻿#version 330 core
+
+// Defined by blur/bokeh.rs:
+// KERNEL_RADIUS, the number of taps on either side of the centre.
+// COMPONENT_COUNT, the number of complex components of the bokeh kernel.
+
+const int KERNEL_SIZE = KERNEL_RADIUS * 2 + 1;
+
+// The horizontal passes, two components per texture.
+uniform sampler2D TextureR0;
+uniform sampler2D TextureG0;
+uniform sampler2D TextureB0;
+#if COMPONENT_COUNT > 2
+uniform sampler2D TextureR1;
+uniform sampler2D TextureG1;
+uniform sampler2D TextureB1;
+#endif
+uniform vec2 Resolution;
+uniform vec2[COMPONENT_COUNT * KERNEL_SIZE] Kernels;
+uniform vec2[COMPONENT_COUNT] Weights;
+uniform float FilterRadius;
+
+in VS_OUTPUT {
+    vec3 Position;
+    vec4 Color;
+    vec2 Uv;
+} IN;
+
+out vec4 Color;
+
+//(Pr+Pi)*(Qr+Qi) = (Pr*Qr+Pr*Qi+Pi*Qr-Pi*Qi)
+vec2 multComplex(vec2 p, vec2 q)
+{
+    return vec2(p.x*q.x-p.y*q.y, p.x*q.y+p.y*q.x);
+}
+
+// The weighted real part of a horizontally convolved texel convolved with a component once more.
+float convolve(vec2 texel, int component, int pixel)
+{
+    return dot(multComplex(texel, Kernels[component * KERNEL_SIZE + pixel]), Weights[component]);
+}
+
+void main()
+{
+    vec2 stepVal = 1.0 / Resolution;
+
+    vec3 color = vec3(0.0);
+
+    for (int i=-KERNEL_RADIUS; i <=KERNEL_RADIUS; ++i)
+    {
+        vec2 coords = IN.Uv + stepVal * vec2(0.0, float(i)) * FilterRadius;
+
+        int pixel = int(i + KERNEL_RADIUS);
+
+        vec4 imageTexelR = texture(TextureR0, coords);
+        vec4 imageTexelG = texture(TextureG0, coords);
+        vec4 imageTexelB = texture(TextureB0, coords);
+
+        color += vec3(
+            convolve(imageTexelR.xy, 0, pixel),
+            convolve(imageTexelG.xy, 0, pixel),
+            convolve(imageTexelB.xy, 0, pixel));
+
+#if COMPONENT_COUNT > 1
+        color += vec3(
+            convolve(imageTexelR.zw, 1, pixel),
+            convolve(imageTexelG.zw, 1, pixel),
+            convolve(imageTexelB.zw, 1, pixel));
+#endif
+
+#if COMPONENT_COUNT > 2
+        imageTexelR = texture(TextureR1, coords);
+        imageTexelG = texture(TextureG1, coords);
+        imageTexelB = texture(TextureB1, coords);
+
+        color += vec3(
+            convolve(imageTexelR.xy, 2, pixel),
+            convolve(imageTexelG.xy, 2, pixel),
+            convolve(imageTexelB.xy, 2, pixel));
+#endif
+    }
+
+    Color = vec4(color, 1.0);
+}
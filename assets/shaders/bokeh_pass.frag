@@ -0,0 +1,45 @@
+﻿#version 330 core
+
+// Defined by blur/bokeh.rs:
+// KERNEL_RADIUS, the number of taps on either side of the centre.
+// COMPONENT_COUNT, the number of complex components of the bokeh kernel.
+// CHANNEL, the colour channel this pass blurs.
+// FIRST_COMPONENT, the first of the up to two components this pass convolves with.
+
+const int KERNEL_SIZE = KERNEL_RADIUS * 2 + 1;
+
+uniform sampler2D Texture;
+uniform vec2 Resolution;
+uniform vec2[COMPONENT_COUNT * KERNEL_SIZE] Kernels;
+uniform float FilterRadius;
+
+in VS_OUTPUT {
+    vec3 Position;
+    vec4 Color;
+    vec2 Uv;
+} IN;
+
+out vec4 Color;
+
+void main()
+{
+    vec2 stepVal = 1.0 / Resolution;
+
+    vec4 val = vec4(0, 0, 0, 0);
+
+    for (int i=-KERNEL_RADIUS; i <=KERNEL_RADIUS; ++i)
+    {
+        vec2 coords = IN.Uv + stepVal * vec2(float(i), 0.0) * FilterRadius;
+
+        float imageTexel = texture(Texture, coords).CHANNEL;
+
+        int pixel = int(i + KERNEL_RADIUS);
+
+        val.xy += imageTexel * Kernels[FIRST_COMPONENT * KERNEL_SIZE + pixel];
+#if FIRST_COMPONENT + 1 < COMPONENT_COUNT
+        val.zw += imageTexel * Kernels[(FIRST_COMPONENT + 1) * KERNEL_SIZE + pixel];
+#endif
+    }
+
+    Color = val;
+}
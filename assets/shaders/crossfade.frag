@@ -0,0 +1,19 @@
+﻿#version 330 core
+
+uniform sampler2D Previous;
+uniform sampler2D Texture;
+// How far the fade from the previous texture to the other has come, from 0 to 1.
+uniform float Crossfade;
+
+in VS_OUTPUT {
+    vec3 Position;
+    vec4 Color;
+    vec2 Uv;
+} IN;
+
+out vec4 Color;
+
+void main()
+{
+    Color = mix(texture(Previous, IN.Uv), texture(Texture, IN.Uv), Crossfade);
+}
@@ -2,6 +2,7 @@
 
 uniform sampler2D Texture;
 uniform vec2 Resolution;
+uniform float Opacity;
 
 in VS_OUTPUT {
     vec3 Position;
@@ -33,7 +34,7 @@ void main()
     // Offset to accommodate for texture edges.
     target_uv = target_uv * correction + (1.0 - correction) * 0.5;
 
-    vec3 color = texture(Texture, target_uv).rgb;
+    vec3 color = sampleBackground(Texture, target_uv);
 
     float opacity = smoothstep(0.0, 0.1, 1.0 - length(uv));
 
@@ -43,5 +44,5 @@ void main()
 //    float lightness = mix(min_lightness, max_lightness, 1.0 - pow(length(uv), lightness_power));
     float lightness = 1.0;
 
-    Color = vec4(color * lightness, opacity);
+    Color = vec4(color * lightness, opacity * Opacity);
 }
@@ -4,6 +4,7 @@ layout (location = 0) in vec3 Position;
 layout (location = 1) in vec4 Color;
 layout (location = 2) in vec2 Uv;
 layout (location = 3) in vec3 Offset;
+layout (location = 4) in vec2 Wobble; // x: seconds since the last merge, y: amplitude.
 
 uniform mat4 MVP;
 
@@ -14,14 +15,23 @@ out VS_OUTPUT {
     vec3 Offset;
 } OUT;
 
+const float WOBBLE_FREQUENCY = 25.0;
+const float WOBBLE_DAMPING = 6.0;
+
 void main()
 {
-    vec4 pos = vec4(Position.xy * Offset.z + Offset.xy, Position.z, 1.0);
+    float wobble = Wobble.y * exp(-WOBBLE_DAMPING * Wobble.x) * sin(WOBBLE_FREQUENCY * Wobble.x);
+
+    // The radius pulses while the droplet is stretched on one axis and squashed on the other.
+    float size = Offset.z * (1.0 + 0.5 * wobble);
+    vec2 shape = vec2(1.0 + wobble, 1.0 / (1.0 + wobble));
+
+    vec4 pos = vec4(Position.xy * shape * size + Offset.xy, Position.z, 1.0);
 
     gl_Position = MVP * pos;
 
     OUT.Position = pos.xyz;
     OUT.Color = Color;
     OUT.Uv = Uv;
-    OUT.Offset = Offset;
+    OUT.Offset = vec3(Offset.xy, size);
 }
@@ -16,7 +16,7 @@ void main()
 {
     vec4 mask = texture(Mask, IN.Uv);
     vec4 tex0 = texture(Texture0, IN.Uv);
-    vec4 tex1 = texture(Texture1, IN.Uv);
+    vec4 tex1 = vec4(sampleBackground(Texture1, IN.Uv), 1.0);
 
     Color = mix(tex0, tex1, clamp(mask.r, 0.0, 1.0)) * IN.Color;
 }
@@ -0,0 +1,18 @@
+﻿#version 330 core
+
+uniform sampler2D Frost;
+
+in VS_OUTPUT {
+    vec3 Position;
+    vec4 Color;
+    vec2 Uv;
+} IN;
+
+out vec4 Color;
+
+void main()
+{
+    float ice = texture(Frost, IN.Uv).r;
+
+    Color = vec4(IN.Color.rgb, IN.Color.a * ice);
+}
@@ -0,0 +1,31 @@
+﻿#version 330 core
+
+// Defined by blur/gaussian.rs:
+// KERNEL_RADIUS, the number of taps on either side of the centre.
+
+uniform sampler2D Texture;
+// The distance between two taps, in texture coordinates, along the direction of the pass.
+uniform vec2 Step;
+// The weights of the centre tap and the taps on either side of it.
+uniform float[KERNEL_RADIUS + 1] Weights;
+
+in VS_OUTPUT {
+    vec3 Position;
+    vec4 Color;
+    vec2 Uv;
+} IN;
+
+out vec4 Color;
+
+void main()
+{
+    vec3 color = texture(Texture, IN.Uv).rgb * Weights[0];
+
+    for (int i = 1; i <= KERNEL_RADIUS; ++i)
+    {
+        color += texture(Texture, IN.Uv + Step * float(i)).rgb * Weights[i];
+        color += texture(Texture, IN.Uv - Step * float(i)).rgb * Weights[i];
+    }
+
+    Color = vec4(color, 1.0);
+}
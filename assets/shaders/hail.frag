@@ -0,0 +1,30 @@
+﻿#version 330 core
+
+uniform float Opacity;
+
+in VS_OUTPUT {
+    vec2 Uv;
+    float Progress;
+    float Seed;
+} IN;
+
+out vec4 Color;
+
+void main()
+{
+    vec2 uv = (IN.Uv * 2.0 - 1.0);
+    float radius = length(uv);
+
+    // A bright flash where the stone hit, and a splash ring running outwards.
+    float flash = smoothstep(0.4, 0.0, radius) * (1.0 - smoothstep(0.0, 0.3, IN.Progress));
+
+    float ring_radius = 0.3 + 0.7 * IN.Progress;
+    float ring = smoothstep(0.12, 0.0, abs(radius - ring_radius));
+
+    // Ragged droplets make the ring look splashed rather than drawn.
+    float ragged = 0.6 + 0.4 * cos(atan(uv.y, uv.x) * 9.0 + IN.Seed * 40.0);
+
+    float opacity = max(flash, ring * ragged * 0.6) * (1.0 - IN.Progress);
+
+    Color = vec4(vec3(0.9, 0.95, 1.0), opacity * Opacity);
+}
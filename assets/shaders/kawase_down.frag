@@ -0,0 +1,25 @@
+﻿#version 330 core
+
+uniform sampler2D Texture;
+// Half a pixel of the target, which is half the size of the texture, scaled by the offset.
+uniform vec2 HalfPixel;
+
+in VS_OUTPUT {
+    vec3 Position;
+    vec4 Color;
+    vec2 Uv;
+} IN;
+
+out vec4 Color;
+
+void main()
+{
+    vec3 color = texture(Texture, IN.Uv).rgb * 4.0;
+
+    color += texture(Texture, IN.Uv - HalfPixel).rgb;
+    color += texture(Texture, IN.Uv + HalfPixel).rgb;
+    color += texture(Texture, IN.Uv + vec2(HalfPixel.x, -HalfPixel.y)).rgb;
+    color += texture(Texture, IN.Uv - vec2(HalfPixel.x, -HalfPixel.y)).rgb;
+
+    Color = vec4(color / 8.0, 1.0);
+}
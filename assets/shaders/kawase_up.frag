@@ -0,0 +1,30 @@
+﻿#version 330 core
+
+uniform sampler2D Texture;
+// Half a pixel of the target, which is twice the size of the texture, scaled by the offset.
+uniform vec2 HalfPixel;
+
+in VS_OUTPUT {
+    vec3 Position;
+    vec4 Color;
+    vec2 Uv;
+} IN;
+
+out vec4 Color;
+
+void main()
+{
+    vec3 color = vec3(0.0);
+
+    // The corners of a diamond, and the diagonals in between weighted twice.
+    color += texture(Texture, IN.Uv + vec2(-HalfPixel.x * 2.0, 0.0)).rgb;
+    color += texture(Texture, IN.Uv + vec2(HalfPixel.x * 2.0, 0.0)).rgb;
+    color += texture(Texture, IN.Uv + vec2(0.0, -HalfPixel.y * 2.0)).rgb;
+    color += texture(Texture, IN.Uv + vec2(0.0, HalfPixel.y * 2.0)).rgb;
+    color += texture(Texture, IN.Uv + vec2(-HalfPixel.x, HalfPixel.y)).rgb * 2.0;
+    color += texture(Texture, IN.Uv + vec2(HalfPixel.x, HalfPixel.y)).rgb * 2.0;
+    color += texture(Texture, IN.Uv + vec2(HalfPixel.x, -HalfPixel.y)).rgb * 2.0;
+    color += texture(Texture, IN.Uv + vec2(-HalfPixel.x, -HalfPixel.y)).rgb * 2.0;
+
+    Color = vec4(color / 12.0, 1.0);
+}
@@ -0,0 +1,26 @@
+﻿#version 330 core
+
+uniform sampler2D Texture;
+// The level of detail to sample, the larger the blurrier.
+uniform float Lod;
+
+in VS_OUTPUT {
+    vec3 Position;
+    vec4 Color;
+    vec2 Uv;
+} IN;
+
+out vec4 Color;
+
+void main()
+{
+    // Four taps around every pixel hide the blocks of the smaller levels somewhat.
+    vec2 halfTexel = 0.5 / vec2(textureSize(Texture, int(Lod)));
+
+    vec3 color = textureLod(Texture, IN.Uv + vec2(-halfTexel.x, -halfTexel.y), Lod).rgb;
+    color += textureLod(Texture, IN.Uv + vec2(halfTexel.x, -halfTexel.y), Lod).rgb;
+    color += textureLod(Texture, IN.Uv + vec2(-halfTexel.x, halfTexel.y), Lod).rgb;
+    color += textureLod(Texture, IN.Uv + vec2(halfTexel.x, halfTexel.y), Lod).rgb;
+
+    Color = vec4(color / 4.0, 1.0);
+}
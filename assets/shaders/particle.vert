@@ -0,0 +1,30 @@
+﻿#version 330 core
+
+layout (location = 0) in vec3 Position;
+layout (location = 1) in vec4 Color;
+layout (location = 2) in vec2 Uv;
+layout (location = 3) in vec3 Offset;
+layout (location = 4) in vec2 State; // x: progress through the particle's lifetime, y: seed.
+
+uniform mat4 MVP;
+
+out VS_OUTPUT {
+    vec2 Uv;
+    float Progress;
+    float Seed;
+} OUT;
+
+void main()
+{
+    // Every particle is turned differently, so they do not all look alike.
+    float angle = State.y * 6.2831853;
+    mat2 rotation = mat2(cos(angle), sin(angle), -sin(angle), cos(angle));
+
+    vec4 pos = vec4(rotation * Position.xy * Offset.z + Offset.xy, Position.z, 1.0);
+
+    gl_Position = MVP * pos;
+
+    OUT.Uv = Uv;
+    OUT.Progress = State.x;
+    OUT.Seed = State.y;
+}
@@ -0,0 +1,33 @@
+﻿#version 330 core
+
+uniform float Opacity;
+
+in VS_OUTPUT {
+    vec2 Uv;
+    float Progress;
+    float Seed;
+} IN;
+
+out vec4 Color;
+
+const float ARM_COUNT = 6.0;
+
+void main()
+{
+    vec2 uv = (IN.Uv * 2.0 - 1.0);
+
+    // Melting flakes shrink and lose their arms until only a wet blob is left.
+    float melt = IN.Progress;
+    float radius = length(uv) / (1.0 - 0.5 * melt);
+    float angle = atan(uv.y, uv.x);
+
+    float arms = pow(abs(cos(angle * ARM_COUNT * 0.5)), mix(8.0, 0.5, melt));
+    float branches = 0.5 + 0.5 * cos(radius * (10.0 + 6.0 * IN.Seed));
+
+    float shape = mix(arms * mix(0.6, 1.0, branches), 1.0, melt * melt);
+    float core = smoothstep(0.35, 0.0, radius);
+
+    float opacity = max(shape * smoothstep(1.0, 0.7, radius), core);
+
+    Color = vec4(vec3(0.95, 0.97, 1.0), opacity * mix(0.9, 0.4, melt) * Opacity);
+}
@@ -0,0 +1,34 @@
+﻿#version 330 core
+
+uniform sampler2D Texture;
+uniform vec2 Resolution;
+uniform float Opacity;
+
+in VS_OUTPUT {
+    vec2 Normal;
+    vec2 Uv;
+} IN;
+
+out vec4 Color;
+
+// How far the streak bends the background across its width, in pixels.
+const float REFRACTION = 6.0;
+
+void main()
+{
+    // Same as the droplets, so streaks and the droplets laying them line up.
+    const float correction = 0.8;
+
+    float across = IN.Uv.x * 2.0 - 1.0;
+
+    // A streak is a flat cylinder, so it only bends the background across its width.
+    vec2 target_uv = (gl_FragCoord.xy - IN.Normal * across * REFRACTION) / Resolution;
+
+    target_uv = target_uv * correction + (1.0 - correction) * 0.5;
+
+    vec3 color = sampleBackground(Texture, target_uv);
+
+    float opacity = smoothstep(0.0, 0.3, 1.0 - abs(across)) * IN.Uv.y;
+
+    Color = vec4(color, opacity * Opacity);
+}
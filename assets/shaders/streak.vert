@@ -0,0 +1,20 @@
+﻿#version 330 core
+
+layout (location = 0) in vec3 Position;
+layout (location = 1) in vec2 Normal;
+layout (location = 2) in vec2 Uv; // x: across the streak, y: opacity.
+
+uniform mat4 MVP;
+
+out VS_OUTPUT {
+    vec2 Normal;
+    vec2 Uv;
+} OUT;
+
+void main()
+{
+    gl_Position = MVP * vec4(Position, 1.0);
+
+    OUT.Normal = Normal;
+    OUT.Uv = Uv;
+}
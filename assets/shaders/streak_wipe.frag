@@ -0,0 +1,17 @@
+﻿#version 330 core
+
+in VS_OUTPUT {
+    vec2 Normal;
+    vec2 Uv;
+} IN;
+
+out vec4 Color;
+
+void main()
+{
+    float across = IN.Uv.x * 2.0 - 1.0;
+
+    float opacity = smoothstep(0.0, 0.3, 1.0 - abs(across));
+
+    Color = vec4(vec3(1.0), opacity * IN.Uv.y);
+}
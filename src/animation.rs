@@ -0,0 +1,450 @@
+﻿use crate::background_loader::{self, SizeLimit};
+use crate::render_gl::Texture;
+use crate::slideshow;
+use image::codecs::gif::GifDecoder;
+use image::codecs::png::PngDecoder;
+use image::AnimationDecoder;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::thread;
+use std::time::Duration;
+
+// Frames are uploaded in turn into this many textures, so a new frame never overwrites the one
+// still being drawn, nor the one a crossfade or blur may still hold on to.
+const RING_SIZE: usize = 3;
+
+// Decoded frames waiting to be shown, at most.
+const QUEUE_SIZE: usize = 2;
+
+// Frames of a sequence are numbered where the path has a run of this character.
+const FRAME_NUMBER_PLACEHOLDER: char = '#';
+
+// Browsers show GIF and APNG frames without a delay for this long.
+const DEFAULT_FRAME_DELAY: f32 = 0.1;
+
+#[derive(Debug, Fail)]
+pub enum Error {
+    #[fail(display = "No frames in {}", path)]
+    NoFrames { path: String },
+}
+
+/// Whether `path` is an animated GIF or PNG, or a numbered frame sequence, like
+/// `street_####.png` or a directory of nothing but numbered frames.
+pub fn is_animation(path: &Path) -> bool {
+    frame_pattern(path).is_some() || container(path).is_some() || directory_frames(path).is_some()
+}
+
+/// The files that hold all frames of an animation.
+#[derive(Clone, Copy)]
+enum Container {
+    Gif,
+    Apng,
+}
+
+fn container(path: &Path) -> Option<Container> {
+    let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+
+    match extension.as_str() {
+        "gif" => Some(Container::Gif),
+        "apng" => Some(Container::Apng),
+        // Only some PNG files are animated, which takes reading the file to tell.
+        "png" => {
+            let decoder = PngDecoder::new(BufReader::new(File::open(path).ok()?)).ok()?;
+
+            if decoder.is_apng() {
+                Some(Container::Apng)
+            } else {
+                None
+            }
+        }
+        _ => None,
+    }
+}
+
+struct Frame {
+    image: image::RgbaImage,
+    // Seconds the frame is shown for.
+    delay: f32,
+}
+
+/// A looping animated background, decoded on a worker thread a couple of frames ahead, so only a
+/// few frames are ever held in memory.
+///
+/// Frames advance on the simulation clock. If the next frame is not decoded by the time it is
+/// due, the current one is simply shown a little longer.
+pub struct Animation {
+    gl: gl::Gl,
+    format: gl::types::GLenum,
+    frames: Receiver<Frame>,
+    // Empty until the first frame is decoded.
+    ring: Vec<Rc<Texture>>,
+    // Index of the texture in `ring` the current frame is in.
+    slot: usize,
+    delay: f32,
+    elapsed: f32,
+}
+
+impl Animation {
+    /// Opens an animated GIF or PNG, or a frame sequence shown at `frames_per_second`, without
+    /// waiting for its first frame. Frames are decoded no larger than `limit`, and stored as sRGB
+    /// if `srgb` is set.
+    ///
+    /// The first frame is saved as `thumbnail`, to show while the animation loads the next time.
+    pub fn open(
+        gl: &gl::Gl,
+        path: &Path,
+        frames_per_second: f32,
+        limit: SizeLimit,
+        thumbnail: PathBuf,
+        srgb: bool,
+    ) -> Result<Animation, failure::Error> {
+        let (sender, frames) = mpsc::sync_channel(QUEUE_SIZE);
+
+        let sequence = match frame_pattern(path) {
+            Some((prefix, digits, suffix)) => Some(sequence_paths(&prefix, digits, &suffix)),
+            None => directory_frames(path),
+        };
+
+        match sequence {
+            Some(paths) => {
+                if paths.is_empty() {
+                    return Err(Error::NoFrames {
+                        path: path.to_string_lossy().into_owned(),
+                    }
+                    .into());
+                }
+
+                let delay = 1.0 / frames_per_second;
+
+                thread::spawn(move || decode_sequence(paths, delay, limit, thumbnail, sender));
+            }
+            None => {
+                let container = container(path).ok_or_else(|| Error::NoFrames {
+                    path: path.to_string_lossy().into_owned(),
+                })?;
+
+                // Reads just the header, to report a broken file right away rather than from the
+                // worker.
+                open_frames(path, container).map_err(|e| background_loader::Error::new(path, e))?;
+
+                let path = path.to_path_buf();
+
+                thread::spawn(move || decode_animation(path, container, limit, thumbnail, sender));
+            }
+        }
+
+        Ok(Animation {
+            gl: gl.clone(),
+            format: if srgb { gl::SRGB8_ALPHA8 } else { gl::RGBA8 },
+            frames,
+            ring: Vec::new(),
+            slot: 0,
+            delay: 0.0,
+            elapsed: 0.0,
+        })
+    }
+
+    /// Whether the first frame has been shown.
+    pub fn is_playing(&self) -> bool {
+        !self.ring.is_empty()
+    }
+
+    /// The texture the current frame is in, once the animation is playing.
+    pub fn texture(&self) -> Rc<Texture> {
+        self.ring[self.slot].clone()
+    }
+
+    /// Advances the animation, returning whether it moved on to another frame, the first one
+    /// included.
+    pub fn update(&mut self, dt: &Duration) -> bool {
+        if !self.is_playing() {
+            return self.start();
+        }
+
+        self.elapsed += dt.as_secs_f32();
+
+        if self.elapsed < self.delay {
+            return false;
+        }
+
+        let frame = match self.frames.try_recv() {
+            Ok(frame) => frame,
+            Err(_) => return false,
+        };
+
+        self.slot = (self.slot + 1) % RING_SIZE;
+
+        self.upload(&frame);
+
+        // A long pause, like a fast forward, skips ahead rather than racing through frames.
+        self.elapsed = (self.elapsed - self.delay).min(frame.delay);
+        self.delay = frame.delay;
+
+        true
+    }
+
+    /// Shows the first frame, once it is decoded. Every frame goes into textures of its size.
+    fn start(&mut self) -> bool {
+        // The worker reports why if it gives up before the first frame.
+        let first = match self.frames.try_recv() {
+            Ok(frame) => frame,
+            Err(_) => return false,
+        };
+
+        let (width, height) = first.image.dimensions();
+
+        for _ in 0..RING_SIZE {
+            match Texture::new_with_format(&self.gl, width, height, self.format) {
+                Ok(texture) => self.ring.push(Rc::new(texture)),
+                Err(e) => {
+                    println!("Failed to show animation: {}", e);
+
+                    // Stops the worker, which gives up once nobody receives its frames.
+                    self.frames = mpsc::sync_channel(0).1;
+                    self.ring.clear();
+
+                    return false;
+                }
+            }
+        }
+
+        self.delay = first.delay;
+        self.upload(&first);
+
+        true
+    }
+
+    fn upload(&self, frame: &Frame) {
+        let texture = &self.ring[self.slot];
+
+        texture.update_from_rgba_image(&frame.image);
+        texture.generate_mipmaps();
+    }
+}
+
+/// Splits a path like `street_####.png` into the parts before and after the frame number, and
+/// the number of digits. Only the file name is numbered.
+fn frame_pattern(path: &Path) -> Option<(String, usize, String)> {
+    let file_name = path.file_name()?.to_str()?;
+
+    let start = file_name.find(FRAME_NUMBER_PLACEHOLDER)?;
+    let digits = file_name[start..]
+        .chars()
+        .take_while(|c| *c == FRAME_NUMBER_PLACEHOLDER)
+        .count();
+
+    let prefix = path.parent()?.join(&file_name[..start]);
+
+    Some((
+        prefix.to_str()?.to_string(),
+        digits,
+        file_name[start + digits..].to_string(),
+    ))
+}
+
+/// The frames of a sequence, from the first number that exists on until the first that does not.
+fn sequence_paths(prefix: &str, digits: usize, suffix: &str) -> Vec<PathBuf> {
+    let path = |number: usize| {
+        PathBuf::from(format!(
+            "{}{:0width$}{}",
+            prefix,
+            number,
+            suffix,
+            width = digits
+        ))
+    };
+
+    // Sequences commonly start at either 0 or 1.
+    let first = if path(0).is_file() { 0 } else { 1 };
+
+    (first..)
+        .map(path)
+        .take_while(|path| path.is_file())
+        .collect()
+}
+
+/// The frames of a directory that holds nothing but consecutively numbered images, like
+/// `0001.png`, `0002.png` and so on, in order. Files other than images do not count.
+fn directory_frames(directory: &Path) -> Option<Vec<PathBuf>> {
+    if !directory.is_dir() {
+        return None;
+    }
+
+    let mut pattern = None;
+    let mut frames = Vec::new();
+
+    for entry in std::fs::read_dir(directory).ok()? {
+        let path = entry.ok()?.path();
+
+        if !path.is_file() || !slideshow::is_image(&path) {
+            continue;
+        }
+
+        let (number, parts) = split_number(path.file_name()?.to_str()?)?;
+
+        // Every frame is named alike, apart from its number.
+        if *pattern.get_or_insert_with(|| parts.clone()) != parts {
+            return None;
+        }
+
+        frames.push((number, path));
+    }
+
+    frames.sort();
+
+    // A gap in the numbers is more like a set of photos than an animation.
+    let consecutive = frames.windows(2).all(|pair| pair[1].0 == pair[0].0 + 1);
+
+    if frames.len() < 2 || !consecutive {
+        return None;
+    }
+
+    Some(frames.into_iter().map(|(_, path)| path).collect())
+}
+
+/// Splits a file name at its last run of digits, into the number and the parts around it.
+fn split_number(file_name: &str) -> Option<(u64, (String, String))> {
+    let end = file_name.rfind(|c: char| c.is_ascii_digit())? + 1;
+    let start = file_name[..end]
+        .rfind(|c: char| !c.is_ascii_digit())
+        .map_or(0, |i| i + 1);
+
+    let number = file_name[start..end].parse().ok()?;
+
+    Some((
+        number,
+        (file_name[..start].to_string(), file_name[end..].to_string()),
+    ))
+}
+
+fn decode_sequence(
+    paths: Vec<PathBuf>,
+    delay: f32,
+    limit: SizeLimit,
+    thumbnail: PathBuf,
+    sender: SyncSender<Frame>,
+) {
+    let mut thumbnail = Some(thumbnail);
+    let mut size = None;
+    let mut paths = paths;
+    let mut index = 0;
+
+    while !paths.is_empty() {
+        index %= paths.len();
+
+        // Frames are turned upright and scaled down like any other background.
+        let image = match background_loader::open(&paths[index], limit) {
+            Ok(image) => image.into_rgba8(),
+            Err(e) => {
+                println!("{}", e);
+
+                // Skipped from then on, rather than reported on every loop.
+                paths.remove(index);
+
+                continue;
+            }
+        };
+
+        index += 1;
+
+        let (width, height) = *size.get_or_insert(image.dimensions());
+
+        // Every frame goes into textures of the size of the first.
+        let image = if image.dimensions() != (width, height) {
+            image::imageops::resize(&image, width, height, image::imageops::FilterType::Triangle)
+        } else {
+            image
+        };
+
+        // Stops once the animation is dropped.
+        if !send(&sender, Frame { image, delay }, &mut thumbnail) {
+            return;
+        }
+    }
+}
+
+/// Decodes the frames of a GIF or APNG one at a time, opening the file again to loop.
+fn decode_animation(
+    path: PathBuf,
+    container: Container,
+    limit: SizeLimit,
+    thumbnail: PathBuf,
+    sender: SyncSender<Frame>,
+) {
+    let mut thumbnail = Some(thumbnail);
+
+    loop {
+        let frames = match open_frames(&path, container) {
+            Ok(frames) => frames,
+            Err(e) => {
+                println!("{}", background_loader::Error::new(&path, e));
+                return;
+            }
+        };
+
+        let mut count = 0;
+
+        for frame in frames {
+            let frame = match frame {
+                Ok(frame) => frame,
+                Err(e) => {
+                    // The frames decoded so far still loop.
+                    println!("{}", background_loader::Error::new(&path, e));
+                    break;
+                }
+            };
+
+            count += 1;
+
+            let (numer, denom) = frame.delay().numer_denom_ms();
+            let delay = numer as f32 / denom as f32 / 1000.0;
+
+            let frame = Frame {
+                image: background_loader::scale_down(
+                    image::DynamicImage::ImageRgba8(frame.into_buffer()),
+                    limit,
+                )
+                .into_rgba8(),
+                delay: if delay > 0.01 {
+                    delay
+                } else {
+                    DEFAULT_FRAME_DELAY
+                },
+            };
+
+            // Stops once the animation is dropped.
+            if !send(&sender, frame, &mut thumbnail) {
+                return;
+            }
+        }
+
+        if count == 0 {
+            return;
+        }
+    }
+}
+
+/// Hands a frame to the animation, saving the first one as the thumbnail. Returns whether the
+/// animation is still there.
+fn send(sender: &SyncSender<Frame>, frame: Frame, thumbnail: &mut Option<PathBuf>) -> bool {
+    if let Some(path) = thumbnail.take() {
+        background_loader::save_thumbnail(
+            &image::DynamicImage::ImageRgba8(frame.image.clone()),
+            &path,
+        );
+    }
+
+    sender.send(frame).is_ok()
+}
+
+fn open_frames(path: &Path, container: Container) -> image::ImageResult<image::Frames<'static>> {
+    let reader = BufReader::new(File::open(path)?);
+
+    Ok(match container {
+        Container::Gif => GifDecoder::new(reader)?.into_frames(),
+        Container::Apng => PngDecoder::new(reader)?.apng().into_frames(),
+    })
+}
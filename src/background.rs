@@ -1,354 +1,319 @@
-﻿use crate::quad;
-use crate::render_gl::{Error, FrameBuffer, Program, Shader, Texture};
+﻿use crate::blur::{self, BlurFilter};
+use crate::config::BackgroundFit;
+use crate::quad::Quad;
+use crate::render_gl::{with_prelude, FrameBuffer, Program, Texture};
 use nalgebra as na;
+use std::cell::Cell;
 use std::rc::Rc;
+use std::time::Duration;
 
-const BACKGROUND_VERT: &str = include_str!("../assets/shaders/background.vert");
 const BACKGROUND_FRAG: &str = include_str!("../assets/shaders/background.frag");
-const BACKGROUND_PASS_RED_FRAG: &str = include_str!("../assets/shaders/background_pass_red.frag");
-const BACKGROUND_PASS_GREEN_FRAG: &str =
-    include_str!("../assets/shaders/background_pass_green.frag");
-const BACKGROUND_PASS_BLUE_FRAG: &str = include_str!("../assets/shaders/background_pass_blue.frag");
-
-const _KERNEL0BRACKETS_REAL_XY_IM_ZW: [f32; 2] = [0.411259, -0.548794];
-const KERNEL0_REAL_X_IM_Y_REAL_Z_IM_W: [[f32; 4]; 17] = [
-    [
-        /*XY: Non Bracketed*/ 0.014096, -0.022658, /*Bracketed WZ:*/ 0.055991, 0.004413,
-    ],
-    [
-        /*XY: Non Bracketed*/ -0.020612, -0.025574, /*Bracketed WZ:*/ 0.019188, 0.000000,
-    ],
-    [
-        /*XY: Non Bracketed*/ -0.038708, 0.006957, /*Bracketed WZ:*/ 0.000000, 0.049223,
-    ],
-    [
-        /*XY: Non Bracketed*/ -0.021449, 0.040468, /*Bracketed WZ:*/ 0.018301, 0.099929,
-    ],
-    [
-        /*XY: Non Bracketed*/ 0.013015, 0.050223, /*Bracketed WZ:*/ 0.054845, 0.114689,
-    ],
-    [
-        /*XY: Non Bracketed*/ 0.042178, 0.038585, /*Bracketed WZ:*/ 0.085769, 0.097080,
-    ],
-    [
-        /*XY: Non Bracketed*/ 0.057972, 0.019812, /*Bracketed WZ:*/ 0.102517, 0.068674,
-    ],
-    [
-        /*XY: Non Bracketed*/ 0.063647, 0.005252, /*Bracketed WZ:*/ 0.108535, 0.046643,
-    ],
-    [
-        /*XY: Non Bracketed*/ 0.064754, 0.000000, /*Bracketed WZ:*/ 0.109709, 0.038697,
-    ],
-    [
-        /*XY: Non Bracketed*/ 0.063647, 0.005252, /*Bracketed WZ:*/ 0.108535, 0.046643,
-    ],
-    [
-        /*XY: Non Bracketed*/ 0.057972, 0.019812, /*Bracketed WZ:*/ 0.102517, 0.068674,
-    ],
-    [
-        /*XY: Non Bracketed*/ 0.042178, 0.038585, /*Bracketed WZ:*/ 0.085769, 0.097080,
-    ],
-    [
-        /*XY: Non Bracketed*/ 0.013015, 0.050223, /*Bracketed WZ:*/ 0.054845, 0.114689,
-    ],
-    [
-        /*XY: Non Bracketed*/ -0.021449, 0.040468, /*Bracketed WZ:*/ 0.018301, 0.099929,
-    ],
-    [
-        /*XY: Non Bracketed*/ -0.038708, 0.006957, /*Bracketed WZ:*/ 0.000000, 0.049223,
-    ],
-    [
-        /*XY: Non Bracketed*/ -0.020612, -0.025574, /*Bracketed WZ:*/ 0.019188, 0.000000,
-    ],
-    [
-        /*XY: Non Bracketed*/ 0.014096, -0.022658, /*Bracketed WZ:*/ 0.055991, 0.004413,
-    ],
-];
-const _KERNEL1WEIGHTS_REAL_X_IM_Y: [f32; 2] = [0.513282, 4.561110];
-const KERNEL1_REAL_X_IM_Y_REAL_Z_IM_W: [[f32; 4]; 17] = [
-    [
-        /*XY: Non Bracketed*/ 0.000115, 0.009116, /*Bracketed WZ:*/ 0.000000, 0.051147,
-    ],
-    [
-        /*XY: Non Bracketed*/ 0.005324, 0.013416, /*Bracketed WZ:*/ 0.009311, 0.075276,
-    ],
-    [
-        /*XY: Non Bracketed*/ 0.013753, 0.016519, /*Bracketed WZ:*/ 0.024376, 0.092685,
-    ],
-    [
-        /*XY: Non Bracketed*/ 0.024700, 0.017215, /*Bracketed WZ:*/ 0.043940, 0.096591,
-    ],
-    [
-        /*XY: Non Bracketed*/ 0.036693, 0.015064, /*Bracketed WZ:*/ 0.065375, 0.084521,
-    ],
-    [
-        /*XY: Non Bracketed*/ 0.047976, 0.010684, /*Bracketed WZ:*/ 0.085539, 0.059948,
-    ],
-    [
-        /*XY: Non Bracketed*/ 0.057015, 0.005570, /*Bracketed WZ:*/ 0.101695, 0.031254,
-    ],
-    [
-        /*XY: Non Bracketed*/ 0.062782, 0.001529, /*Bracketed WZ:*/ 0.112002, 0.008578,
-    ],
-    [
-        /*XY: Non Bracketed*/ 0.064754, 0.000000, /*Bracketed WZ:*/ 0.115526, 0.000000,
-    ],
-    [
-        /*XY: Non Bracketed*/ 0.062782, 0.001529, /*Bracketed WZ:*/ 0.112002, 0.008578,
-    ],
-    [
-        /*XY: Non Bracketed*/ 0.057015, 0.005570, /*Bracketed WZ:*/ 0.101695, 0.031254,
-    ],
-    [
-        /*XY: Non Bracketed*/ 0.047976, 0.010684, /*Bracketed WZ:*/ 0.085539, 0.059948,
-    ],
-    [
-        /*XY: Non Bracketed*/ 0.036693, 0.015064, /*Bracketed WZ:*/ 0.065375, 0.084521,
-    ],
-    [
-        /*XY: Non Bracketed*/ 0.024700, 0.017215, /*Bracketed WZ:*/ 0.043940, 0.096591,
-    ],
-    [
-        /*XY: Non Bracketed*/ 0.013753, 0.016519, /*Bracketed WZ:*/ 0.024376, 0.092685,
-    ],
-    [
-        /*XY: Non Bracketed*/ 0.005324, 0.013416, /*Bracketed WZ:*/ 0.009311, 0.075276,
-    ],
-    [
-        /*XY: Non Bracketed*/ 0.000115, 0.009116, /*Bracketed WZ:*/ 0.000000, 0.051147,
-    ],
-];
+const CROSSFADE_FRAG: &str = include_str!("../assets/shaders/crossfade.frag");
+
+/// The uniforms and functions shaders lay the background out with, to prepend to them with
+/// `with_prelude`.
+pub const LAYOUT_SHADER: &str = include_str!("../assets/shaders/background_layout.glsl");
+
+// Shaders sample the background that fades out from this texture unit, which none of them uses
+// otherwise.
+const PREVIOUS_BACKGROUND_UNIT: u32 = 3;
+
+/// How the background image is laid out on the screen.
+#[derive(Clone, Copy)]
+pub struct Layout {
+    pub fit: BackgroundFit,
+    /// The point cropping keeps in view, as fractions of the width and height of the image from
+    /// its top left corner.
+    pub focus: (f32, f32),
+    pub letterbox_color: (f32, f32, f32),
+}
+
+/// Maps screen coordinates to texture coordinates of the background image, both from 0 to 1,
+/// through `screen * scale + offset`.
+#[derive(Clone, Copy, PartialEq)]
+struct UvTransform {
+    scale: na::Vector2<f32>,
+    offset: na::Vector2<f32>,
+}
+
+impl UvTransform {
+    fn new(layout: &Layout, image_size: (u32, u32), screen_size: (u32, u32)) -> UvTransform {
+        let image = na::Vector2::new(image_size.0 as f32, image_size.1 as f32);
+        let screen = na::Vector2::new(screen_size.0 as f32, screen_size.1 as f32);
+
+        let image_aspect = image.x / image.y;
+        let screen_aspect = screen.x / screen.y;
+
+        // The fraction of the image that spans the screen.
+        let scale = match layout.fit {
+            BackgroundFit::Stretch => na::Vector2::new(1.0, 1.0),
+            BackgroundFit::Fill if image_aspect > screen_aspect => {
+                na::Vector2::new(screen_aspect / image_aspect, 1.0)
+            }
+            BackgroundFit::Fill => na::Vector2::new(1.0, image_aspect / screen_aspect),
+            BackgroundFit::Fit if image_aspect > screen_aspect => {
+                na::Vector2::new(1.0, image_aspect / screen_aspect)
+            }
+            BackgroundFit::Fit => na::Vector2::new(screen_aspect / image_aspect, 1.0),
+            BackgroundFit::Center | BackgroundFit::Tile => screen.component_div(&image),
+        };
+
+        // Textures start at the bottom of the image.
+        let focus = na::Vector2::new(layout.focus.0, 1.0 - layout.focus.1);
+
+        // Cropped images keep their focus at the same place on the screen as in the image, the
+        // others are centred.
+        let offset = na::Vector2::new(
+            (1.0 - scale.x) * if scale.x < 1.0 { focus.x } else { 0.5 },
+            (1.0 - scale.y) * if scale.y < 1.0 { focus.y } else { 0.5 },
+        );
+
+        UvTransform { scale, offset }
+    }
+}
+
+/// Everything the blurred background depends on, to tell when it has to be blurred again.
+#[derive(Clone, Copy, PartialEq)]
+struct BlurInputs {
+    texture: gl::types::GLuint,
+    transform: UvTransform,
+}
+
+/// A background fading out after another one replaced it.
+struct Crossfade {
+    texture: Rc<Texture>,
+    // From 0 to 1.
+    progress: f32,
+    duration: f32,
+}
 
+/// Lays the background texture out on the screen and blurs it with a `BlurFilter`, keeping the
+/// result until the texture changes.
+///
+/// Shaders that sample the background image themselves lay it out the same way through the
+/// uniforms set by `set_layout_uniforms`, which also fade between the previous background and a
+/// new one.
 pub struct Background {
-    program: Program,
     texture: Rc<Texture>,
-    mid_buffer_r: Texture,
-    mid_program_r: Program,
-    mid_buffer_g: Texture,
-    mid_program_g: Program,
-    mid_buffer_b: Texture,
-    mid_program_b: Program,
+    layout: Layout,
+    screen_size: (u32, u32),
+    program: Program,
+    crossfade_program: Program,
+    quad: Quad,
     frame_buffer: FrameBuffer,
-    program_view_location: Option<i32>,
-    program_projection_location: Option<i32>,
-    resolution_location: Option<i32>,
-    quad: quad::Quad,
-    filter_radius: f32,
+    // The background as seen on the screen, before it is blurred.
+    laid_out: Texture,
+    filter: Box<dyn BlurFilter>,
+    blurred: Texture,
+    blurred_inputs: Cell<Option<BlurInputs>>,
+    crossfade: Option<Crossfade>,
+    // The blurred previous background, and the mix of both while they fade.
+    previous_blurred: Texture,
+    crossfaded: Texture,
 }
 
 impl Background {
+    /// Lays out and blurs `texture` into textures of the given format.
     pub fn new(
         gl: &gl::Gl,
         texture: Rc<Texture>,
         screen_width: u32,
         screen_height: u32,
-        filter_radius: f32,
+        format: gl::types::GLenum,
+        layout: Layout,
+        filter: Box<dyn BlurFilter>,
     ) -> Result<Background, failure::Error> {
-        let program = Program::from_shaders(
+        let program = blur::load_program(
             gl,
-            &[
-                Shader::from_vert_source_str(&gl, BACKGROUND_VERT)?,
-                Shader::from_frag_source_str(&gl, BACKGROUND_FRAG)?,
-            ],
-        )
-        .map_err(|msg| Error::LinkError {
-            message: msg,
-            name: "background".to_string(),
-        })?;
-
-        let program_view_location = program.get_uniform_location("View");
-        let program_projection_location = program.get_uniform_location("Projection");
-        let resolution_location = program.get_uniform_location("Resolution");
-
-        let quad =
-            quad::Quad::new_with_size(gl, 0.0, 0.0, screen_height as f32, screen_width as f32);
-
-        let mid_buffer_r = Texture::new(gl, screen_width, screen_height)?;
-        let mid_buffer_g = Texture::new(gl, screen_width, screen_height)?;
-        let mid_buffer_b = Texture::new(gl, screen_width, screen_height)?;
-
-        let background_vert_shader_r = Shader::from_vert_source_str(&gl, BACKGROUND_VERT)?;
-        let background_frag_shader_r = Shader::from_frag_source_str(gl, BACKGROUND_PASS_RED_FRAG)?;
-        let background_vert_shader_g = Shader::from_vert_source_str(&gl, BACKGROUND_VERT)?;
-        let background_frag_shader_g =
-            Shader::from_frag_source_str(gl, BACKGROUND_PASS_GREEN_FRAG)?;
-        let background_vert_shader_b = Shader::from_vert_source_str(&gl, BACKGROUND_VERT)?;
-        let background_frag_shader_b = Shader::from_frag_source_str(gl, BACKGROUND_PASS_BLUE_FRAG)?;
-
-        let mid_program_r =
-            Program::from_shaders(gl, &[background_vert_shader_r, background_frag_shader_r])
-                .map_err(|msg| Error::LinkError {
-                    message: msg,
-                    name: "mid_program_r".to_string(),
-                })?;
-        let mid_program_g =
-            Program::from_shaders(gl, &[background_vert_shader_g, background_frag_shader_g])
-                .map_err(|msg| Error::LinkError {
-                    message: msg,
-                    name: "mid_program_g".to_string(),
-                })?;
-        let mid_program_b =
-            Program::from_shaders(gl, &[background_vert_shader_b, background_frag_shader_b])
-                .map_err(|msg| Error::LinkError {
-                    message: msg,
-                    name: "mid_program_b".to_string(),
-                })?;
-
-        let frame_buffer = FrameBuffer::new(gl);
+            &with_prelude(BACKGROUND_FRAG, LAYOUT_SHADER),
+            &[],
+            "background",
+        )?;
+        let crossfade_program = blur::load_program(gl, CROSSFADE_FRAG, &[], "crossfade")?;
 
         Ok(Background {
             texture,
+            layout,
+            screen_size: (screen_width, screen_height),
             program,
-            mid_buffer_r,
-            mid_program_r,
-            mid_buffer_g,
-            mid_program_g,
-            mid_buffer_b,
-            mid_program_b,
-            frame_buffer,
-            program_view_location,
-            program_projection_location,
-            resolution_location,
-            quad,
-            filter_radius,
+            crossfade_program,
+            quad: blur::full_screen_quad(gl),
+            frame_buffer: FrameBuffer::new(gl),
+            laid_out: Texture::new_with_format(gl, screen_width, screen_height, format)?,
+            filter,
+            blurred: Texture::new_with_format(gl, screen_width, screen_height, format)?,
+            blurred_inputs: Cell::new(None),
+            crossfade: None,
+            previous_blurred: Texture::new_with_format(gl, screen_width, screen_height, format)?,
+            crossfaded: Texture::new_with_format(gl, screen_width, screen_height, format)?,
         })
     }
 
-    pub fn prepass(
-        &self,
-        gl: &gl::Gl,
-        view_matrix: &na::Matrix4<f32>,
-        proj_matrix: &na::Matrix4<f32>,
-        resolution: &na::Vector2<f32>,
-    ) {
-        self.render_pass(
-            gl,
-            &self.mid_program_r,
-            &self.frame_buffer,
-            &self.mid_buffer_r,
-            view_matrix,
-            proj_matrix,
-            resolution,
-        );
+    /// The background image, which shaders lay out through `set_layout_uniforms`.
+    pub fn texture(&self) -> &Texture {
+        &self.texture
+    }
 
-        self.render_pass(
-            gl,
-            &self.mid_program_g,
-            &self.frame_buffer,
-            &self.mid_buffer_g,
-            view_matrix,
-            proj_matrix,
-            resolution,
-        );
+    /// Replaces the background image, fading into it over `crossfade` seconds.
+    pub fn set_texture(&mut self, texture: Rc<Texture>, crossfade: f32) {
+        let previous = std::mem::replace(&mut self.texture, texture);
+
+        // A background that was never blurred is skipped, rather than faded out.
+        if self.blurred_inputs.get().is_some() {
+            std::mem::swap(&mut self.blurred, &mut self.previous_blurred);
+
+            self.crossfade = if crossfade > 0.0 {
+                Some(Crossfade {
+                    texture: previous,
+                    progress: 0.0,
+                    duration: crossfade,
+                })
+            } else {
+                None
+            };
+        }
 
-        self.render_pass(
-            gl,
-            &self.mid_program_b,
-            &self.frame_buffer,
-            &self.mid_buffer_b,
-            view_matrix,
-            proj_matrix,
-            resolution,
-        );
+        self.blurred_inputs.set(None);
     }
 
-    fn render_pass(
-        &self,
-        gl: &gl::Gl,
-        program: &Program,
-        frame_buffer: &FrameBuffer,
-        texture_buffer: &Texture,
-        view_matrix: &na::Matrix4<f32>,
-        proj_matrix: &na::Matrix4<f32>,
-        resolution: &na::Vector2<f32>,
-    ) {
-        program.set_used();
-
-        if let Some(loc) = program.get_uniform_location("Texture") {
-            self.texture.bind_at(0);
-            program.set_uniform_1i(loc, 0);
-        }
+    /// Shows another frame of an animated background, without a crossfade. The blurred
+    /// background keeps showing an earlier frame unless `blur` is set.
+    pub fn set_frame(&mut self, texture: Rc<Texture>, blur: bool) {
+        self.texture = texture;
 
-        if let Some(loc) = program.get_uniform_location("Resolution") {
-            program.set_uniform_2f(loc, resolution);
+        if blur {
+            self.blurred_inputs.set(None);
+        } else if self.blurred_inputs.get().is_some() {
+            self.blurred_inputs.set(Some(self.inputs()));
         }
+    }
 
-        if let Some(loc) = program.get_uniform_location("View") {
-            program.set_uniform_matrix_4fv(loc, view_matrix);
-        }
+    /// Advances the crossfade, if there is one.
+    pub fn step(&mut self, delta: &Duration) {
+        if let Some(crossfade) = self.crossfade.as_mut() {
+            crossfade.progress += delta.as_secs_f32() / crossfade.duration;
 
-        if let Some(loc) = program.get_uniform_location("Projection") {
-            program.set_uniform_matrix_4fv(loc, proj_matrix);
+            if crossfade.progress >= 1.0 {
+                self.crossfade = None;
+            }
         }
+    }
+
+    /// Blurs the background again if anything it depends on changed since the last time.
+    pub fn update(&self, gl: &gl::Gl) {
+        let inputs = self.inputs();
 
-        if let Some(loc) = program.get_uniform_location("FilterRadius") {
-            program.set_uniform_1f(loc, self.filter_radius)
+        let out_of_date = self.blurred_inputs.get() != Some(inputs);
+
+        if !out_of_date && self.crossfade.is_none() {
+            return;
         }
 
-        if let Some(loc) = program.get_uniform_location("Kernel0") {
-            program.set_uniform_4fv(loc, &KERNEL0_REAL_X_IM_Y_REAL_Z_IM_W[..])
+        unsafe {
+            gl.Disable(gl::BLEND);
         }
-        if let Some(loc) = program.get_uniform_location("Kernel1") {
-            program.set_uniform_4fv(loc, &KERNEL1_REAL_X_IM_Y_REAL_Z_IM_W[..])
+
+        if out_of_date {
+            self.program.set_used();
+
+            blur::bind_texture(&self.program, "Texture", &self.texture, 0);
+            self.set_layout_uniforms(&self.program);
+
+            blur::draw(gl, &self.quad, &self.frame_buffer, &self.laid_out);
+
+            self.filter.apply(gl, &self.laid_out, &self.blurred);
+
+            self.blurred_inputs.set(Some(inputs));
         }
 
-        frame_buffer.bind();
-        frame_buffer.attach_texture(texture_buffer);
+        if let Some(crossfade) = self.crossfade.as_ref() {
+            self.crossfade_program.set_used();
 
-        self.quad.render(gl);
+            blur::bind_texture(
+                &self.crossfade_program,
+                "Previous",
+                &self.previous_blurred,
+                0,
+            );
+            blur::bind_texture(&self.crossfade_program, "Texture", &self.blurred, 1);
 
-        frame_buffer.unbind();
-    }
+            if let Some(loc) = self.crossfade_program.get_uniform_location("Crossfade") {
+                self.crossfade_program
+                    .set_uniform_1f(loc, crossfade.progress);
+            }
 
-    pub fn render(
-        &self,
-        gl: &gl::Gl,
-        view_matrix: &na::Matrix4<f32>,
-        proj_matrix: &na::Matrix4<f32>,
-        resolution: &na::Vector2<f32>,
-    ) {
-        self.program.set_used();
-
-        if let Some(loc) = self.program.get_uniform_location("TextureR") {
-            self.mid_buffer_r.bind_at(0);
-            self.program.set_uniform_1i(loc, 0);
+            blur::draw(gl, &self.quad, &self.frame_buffer, &self.crossfaded);
         }
 
-        if let Some(loc) = self.program.get_uniform_location("TextureG") {
-            self.mid_buffer_g.bind_at(1);
-            self.program.set_uniform_1i(loc, 1);
+        unsafe {
+            gl.Enable(gl::BLEND);
         }
+    }
 
-        if let Some(loc) = self.program.get_uniform_location("TextureB") {
-            self.mid_buffer_b.bind_at(2);
-            self.program.set_uniform_1i(loc, 2);
+    /// The blurred background, as of the last `update`.
+    pub fn blurred(&self) -> &Texture {
+        if self.crossfade.is_some() {
+            &self.crossfaded
+        } else {
+            &self.blurred
         }
+    }
+
+    /// Sets the uniforms `sampleBackground` in the shaders lays the background image out with.
+    pub fn set_layout_uniforms(&self, program: &Program) {
+        self.set_transform_uniforms(program, "Background", &self.texture);
 
-        if let Some(loc) = self.program.get_uniform_location("Kernel0") {
-            self.program
-                .set_uniform_4fv(loc, &KERNEL0_REAL_X_IM_Y_REAL_Z_IM_W)
+        if let Some(loc) = program.get_uniform_location("BackgroundTile") {
+            program.set_uniform_1i(loc, (self.layout.fit == BackgroundFit::Tile) as i32);
         }
-        if let Some(loc) = self.program.get_uniform_location("Kernel1") {
-            self.program
-                .set_uniform_4fv(loc, &KERNEL1_REAL_X_IM_Y_REAL_Z_IM_W)
+
+        if let Some(loc) = program.get_uniform_location("LetterboxColor") {
+            let (r, g, b) = self.layout.letterbox_color;
+
+            program.set_uniform_3f(loc, &na::Vector3::new(r, g, b));
         }
 
-        if let Some(loc) = self.program.get_uniform_location("FilterRadius") {
-            self.program.set_uniform_1f(loc, self.filter_radius)
+        let progress = match self.crossfade.as_ref() {
+            Some(crossfade) => {
+                blur::bind_texture(
+                    program,
+                    "PreviousBackground",
+                    &crossfade.texture,
+                    PREVIOUS_BACKGROUND_UNIT,
+                );
+                self.set_transform_uniforms(program, "Previous", &crossfade.texture);
+
+                crossfade.progress
+            }
+            None => 1.0,
+        };
+
+        if let Some(loc) = program.get_uniform_location("Crossfade") {
+            program.set_uniform_1f(loc, progress);
         }
+    }
+
+    /// Sets the `<prefix>Scale` and `<prefix>Offset` uniforms laying out `texture`.
+    fn set_transform_uniforms(&self, program: &Program, prefix: &str, texture: &Texture) {
+        let transform = UvTransform::new(&self.layout, texture.dimensions(), self.screen_size);
 
-        if let Some(loc) = self.resolution_location {
-            self.program.set_uniform_2f(loc, resolution);
+        if let Some(loc) = program.get_uniform_location(&format!("{}Scale", prefix)) {
+            program.set_uniform_2f(loc, &transform.scale);
         }
 
-        if let Some(loc) = self.program_view_location {
-            self.program.set_uniform_matrix_4fv(loc, view_matrix);
+        if let Some(loc) = program.get_uniform_location(&format!("{}Offset", prefix)) {
+            program.set_uniform_2f(loc, &transform.offset);
         }
+    }
 
-        if let Some(loc) = self.program_projection_location {
-            self.program.set_uniform_matrix_4fv(loc, proj_matrix);
+    fn inputs(&self) -> BlurInputs {
+        BlurInputs {
+            texture: self.texture.id(),
+            transform: self.transform(),
         }
+    }
 
-        self.quad.render(gl);
+    fn transform(&self) -> UvTransform {
+        UvTransform::new(&self.layout, self.texture.dimensions(), self.screen_size)
     }
 }
@@ -0,0 +1,227 @@
+﻿use crate::config::{BackgroundFit, ProceduralBackground};
+use crate::procedural;
+use crate::render_gl::Texture;
+use image::GenericImageView;
+use std::fs::File;
+use std::io::BufReader;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+// The longer side of the thumbnail shown while the background loads, in pixels.
+const THUMBNAIL_SIZE: u32 = 64;
+
+// Images are never kept sharper than this many times the screen, beyond which the difference
+// does not show.
+const MAX_SCREEN_SCALE: u32 = 2;
+
+#[derive(Debug, Fail)]
+pub enum Error {
+    #[fail(display = "Failed to read {}: {}", path, message)]
+    Unreadable { path: String, message: String },
+    #[fail(display = "{} is not in a supported image format: {}", path, message)]
+    Unsupported { path: String, message: String },
+    #[fail(display = "Failed to decode {}: {}", path, message)]
+    Damaged { path: String, message: String },
+}
+
+impl Error {
+    /// Tells whether `path` could not be read, is in an unknown format or is damaged.
+    pub fn new(path: &Path, e: image::ImageError) -> Error {
+        let path = path.to_string_lossy().into_owned();
+
+        match e {
+            image::ImageError::IoError(e) => Error::Unreadable {
+                path,
+                message: e.to_string(),
+            },
+            image::ImageError::Unsupported(_) => Error::Unsupported {
+                path,
+                message: e.to_string(),
+            },
+            e => Error::Damaged {
+                path,
+                message: e.to_string(),
+            },
+        }
+    }
+}
+
+/// The largest size worth decoding a background at.
+#[derive(Clone, Copy, Debug)]
+pub struct SizeLimit {
+    max_texture_size: u32,
+    // The size an image is kept covering, if it is scaled to the screen at all.
+    cover: Option<(u32, u32)>,
+}
+
+impl SizeLimit {
+    pub fn new(gl: &gl::Gl, fit: BackgroundFit, screen_size: (u32, u32)) -> SizeLimit {
+        let cover = match fit {
+            BackgroundFit::Fill | BackgroundFit::Fit | BackgroundFit::Stretch => Some((
+                screen_size.0 * MAX_SCREEN_SCALE,
+                screen_size.1 * MAX_SCREEN_SCALE,
+            )),
+            // Shown at their own size, so shrinking them would change what is seen.
+            BackgroundFit::Center | BackgroundFit::Tile => None,
+        };
+
+        SizeLimit {
+            max_texture_size: Texture::max_size(gl),
+            cover,
+        }
+    }
+
+    /// The size to scale an image of the given size down to, if it is too large.
+    fn apply(&self, width: u32, height: u32) -> Option<(u32, u32)> {
+        let mut scale = 1.0f64;
+
+        if let Some((cover_width, cover_height)) = self.cover {
+            scale = scale
+                .min((cover_width as f64 / width as f64).max(cover_height as f64 / height as f64));
+        }
+
+        let max_size = self.max_texture_size as f64;
+
+        scale = scale
+            .min(max_size / width as f64)
+            .min(max_size / height as f64);
+
+        if scale >= 1.0 {
+            return None;
+        }
+
+        let scaled = |size: u32| ((size as f64 * scale).round() as u32).max(1);
+
+        Some((scaled(width), scaled(height)))
+    }
+}
+
+/// Opens a background image, turned upright as its EXIF orientation says, and scaled down to
+/// `limit`.
+pub fn open(path: &Path, limit: SizeLimit) -> Result<image::DynamicImage, failure::Error> {
+    // Some decoders panic on damaged files rather than returning an error.
+    let image = panic::catch_unwind(AssertUnwindSafe(|| image::open(path)))
+        .map_err(|_| Error::Damaged {
+            path: path.to_string_lossy().into_owned(),
+            message: "the decoder crashed".to_string(),
+        })?
+        .map_err(|e| Error::new(path, e))?;
+
+    let image = match orientation(path) {
+        2 => image.fliph(),
+        3 => image.rotate180(),
+        4 => image.flipv(),
+        5 => image.rotate90().fliph(),
+        6 => image.rotate90(),
+        7 => image.rotate270().fliph(),
+        8 => image.rotate270(),
+        _ => image,
+    };
+
+    Ok(scale_down(image, limit))
+}
+
+/// Scales an image down to `limit`, if it is larger.
+pub fn scale_down(image: image::DynamicImage, limit: SizeLimit) -> image::DynamicImage {
+    let (width, height) = image.dimensions();
+
+    match limit.apply(width, height) {
+        Some((width, height)) => {
+            image.resize_exact(width, height, image::imageops::FilterType::Lanczos3)
+        }
+        None => image,
+    }
+}
+
+/// The EXIF orientation of an image, 1 for upright when it has none.
+fn orientation(path: &Path) -> u32 {
+    let exif = File::open(path).ok().and_then(|file| {
+        exif::Reader::new()
+            .read_from_container(&mut BufReader::new(file))
+            .ok()
+    });
+
+    exif.as_ref()
+        .and_then(|exif| exif.get_field(exif::Tag::Orientation, exif::In::PRIMARY))
+        .and_then(|field| field.value.get_uint(0))
+        .unwrap_or(1)
+}
+
+/// A background image decoded on a worker thread, so the rain can start before it is ready.
+pub struct BackgroundLoader {
+    loaded: Receiver<image::DynamicImage>,
+}
+
+impl BackgroundLoader {
+    /// Starts loading the first of `paths` that opens. If none does, `fallback` is generated at
+    /// the size of the screen instead.
+    ///
+    /// A small copy of the image is saved as `thumbnail`, to show while it loads the next time.
+    pub fn start(
+        paths: Vec<PathBuf>,
+        limit: SizeLimit,
+        thumbnail: PathBuf,
+        fallback: ProceduralBackground,
+        screen_size: (u32, u32),
+    ) -> BackgroundLoader {
+        let (sender, loaded) = mpsc::channel();
+
+        thread::spawn(move || {
+            let image = load_first(&paths, limit)
+                .unwrap_or_else(|| procedural::generate(&fallback, screen_size.0, screen_size.1));
+
+            save_thumbnail(&image, &thumbnail);
+
+            // The receiver is gone if the screensaver closed in the meantime.
+            let _ = sender.send(image);
+        });
+
+        BackgroundLoader { loaded }
+    }
+
+    /// The background, once it is decoded.
+    pub fn poll(&self) -> Option<image::DynamicImage> {
+        self.loaded.try_recv().ok()
+    }
+}
+
+/// What to show until the background is loaded: the thumbnail saved the last time, or else a
+/// single pixel of `color`.
+pub fn placeholder(thumbnail: &Path, color: (f32, f32, f32)) -> image::DynamicImage {
+    image::open(thumbnail).unwrap_or_else(|_| {
+        let channel = |c: f32| (c * 255.0).round() as u8;
+
+        image::DynamicImage::ImageRgb8(image::RgbImage::from_pixel(
+            1,
+            1,
+            image::Rgb([channel(color.0), channel(color.1), channel(color.2)]),
+        ))
+    })
+}
+
+fn load_first(paths: &[PathBuf], limit: SizeLimit) -> Option<image::DynamicImage> {
+    for path in paths {
+        match open(path, limit) {
+            Ok(image) => return Some(image),
+            Err(e) => println!("{}", e),
+        }
+    }
+
+    None
+}
+
+/// Saves a small copy of a background, to show while it loads the next time.
+pub fn save_thumbnail(image: &image::DynamicImage, path: &Path) {
+    // Keeps the aspect ratio, so the placeholder is laid out like the image.
+    let thumbnail = image.resize(
+        THUMBNAIL_SIZE,
+        THUMBNAIL_SIZE,
+        image::imageops::FilterType::Triangle,
+    );
+
+    if let Err(e) = thumbnail.save(path) {
+        println!("Failed to save thumbnail {}: {}", path.to_string_lossy(), e);
+    }
+}
@@ -0,0 +1,157 @@
+﻿use std::io::{self, Read, Write};
+
+pub fn read_bool<R: Read>(reader: &mut R) -> io::Result<bool> {
+    let mut buf = [0u8; 1];
+    reader.read_exact(&mut buf)?;
+    Ok(buf[0] != 0)
+}
+
+pub fn read_u32<R: Read>(reader: &mut R) -> io::Result<u32> {
+    let mut buf = [0u8; 4];
+    reader.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+pub fn read_u64<R: Read>(reader: &mut R) -> io::Result<u64> {
+    let mut buf = [0u8; 8];
+    reader.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+pub fn read_u128<R: Read>(reader: &mut R) -> io::Result<u128> {
+    let mut buf = [0u8; 16];
+    reader.read_exact(&mut buf)?;
+    Ok(u128::from_le_bytes(buf))
+}
+
+pub fn read_f32<R: Read>(reader: &mut R) -> io::Result<f32> {
+    read_u32(reader).map(f32::from_bits)
+}
+
+pub fn read_f64<R: Read>(reader: &mut R) -> io::Result<f64> {
+    read_u64(reader).map(f64::from_bits)
+}
+
+pub fn write_bool<W: Write>(writer: &mut W, value: bool) -> io::Result<()> {
+    writer.write_all(&[value as u8])
+}
+
+pub fn write_u32<W: Write>(writer: &mut W, value: u32) -> io::Result<()> {
+    writer.write_all(&value.to_le_bytes())
+}
+
+pub fn write_u64<W: Write>(writer: &mut W, value: u64) -> io::Result<()> {
+    writer.write_all(&value.to_le_bytes())
+}
+
+pub fn write_u128<W: Write>(writer: &mut W, value: u128) -> io::Result<()> {
+    writer.write_all(&value.to_le_bytes())
+}
+
+pub fn write_f32<W: Write>(writer: &mut W, value: f32) -> io::Result<()> {
+    write_u32(writer, value.to_bits())
+}
+
+pub fn write_f64<W: Write>(writer: &mut W, value: f64) -> io::Result<()> {
+    write_u64(writer, value.to_bits())
+}
+
+/// Reads an unsigned LEB128 variable-length integer.
+pub fn read_varint<R: Read>(reader: &mut R) -> io::Result<u64> {
+    let mut value = 0u64;
+    let mut shift = 0;
+
+    loop {
+        let mut buf = [0u8; 1];
+        reader.read_exact(&mut buf)?;
+
+        if shift >= 64 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "varint is too long",
+            ));
+        }
+
+        value |= u64::from(buf[0] & 0x7f) << shift;
+
+        if buf[0] & 0x80 == 0 {
+            return Ok(value);
+        }
+
+        shift += 7;
+    }
+}
+
+/// Writes an unsigned LEB128 variable-length integer, which takes a single byte for values below 128.
+pub fn write_varint<W: Write>(writer: &mut W, mut value: u64) -> io::Result<()> {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+
+        if value == 0 {
+            return writer.write_all(&[byte]);
+        }
+
+        writer.write_all(&[byte | 0x80])?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn varint_bytes(value: u64) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        write_varint(&mut bytes, value).unwrap();
+
+        bytes
+    }
+
+    #[test]
+    fn varints_round_trip() {
+        let values = [
+            0,
+            1,
+            127,
+            128,
+            300,
+            16_383,
+            16_384,
+            u32::MAX as u64,
+            u64::MAX,
+        ];
+
+        for &value in values.iter() {
+            let bytes = varint_bytes(value);
+
+            assert_eq!(read_varint(&mut bytes.as_slice()).unwrap(), value);
+        }
+    }
+
+    #[test]
+    fn varints_take_a_byte_per_seven_bits() {
+        assert_eq!(varint_bytes(0), [0x00]);
+        assert_eq!(varint_bytes(127), [0x7f]);
+        assert_eq!(varint_bytes(128), [0x80, 0x01]);
+        assert_eq!(varint_bytes(300), [0xac, 0x02]);
+        assert_eq!(varint_bytes(u64::MAX).len(), 10);
+    }
+
+    #[test]
+    fn overlong_varint_is_rejected() {
+        let bytes = [0x80; 11];
+
+        let error = read_varint(&mut &bytes[..]).unwrap_err();
+
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn truncated_varint_is_rejected() {
+        let bytes = [0x80, 0x80];
+
+        let error = read_varint(&mut &bytes[..]).unwrap_err();
+
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
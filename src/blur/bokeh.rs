@@ -0,0 +1,212 @@
+﻿use super::{bind_texture, draw, full_screen_quad, load_program, BlurFilter};
+use crate::quad::Quad;
+use crate::render_gl::{FrameBuffer, Program, Texture};
+use nalgebra as na;
+
+const BOKEH_FRAG: &str = include_str!("../../assets/shaders/bokeh.frag");
+const BOKEH_PASS_FRAG: &str = include_str!("../../assets/shaders/bokeh_pass.frag");
+
+// Tap counts are capped, to stay within the uniforms every implementation provides.
+const MAX_KERNEL_RADIUS: usize = 24;
+const MAX_COMPONENT_COUNT: usize = 3;
+
+// The a, b, A and B parameters of the complex Gaussian components approximating a circular
+// bokeh, from "Circularly symmetric convolution and lens blur" by Olli Niemitalo. Every component
+// is exp(-a x^2) (cos(b x^2) + i sin(b x^2)), and the real parts of the results are weighted by
+// A and the imaginary parts by B.
+const COMPONENT_PARAMETERS: [&[[f32; 4]]; MAX_COMPONENT_COUNT] = [
+    &[[0.862325, 1.624835, 0.767583, 1.862321]],
+    &[
+        [0.886528, 5.268909, 0.411259, -0.548794],
+        [1.960518, 1.558213, 0.513282, 4.56111],
+    ],
+    &[
+        [2.17649, 5.043495, 1.621035, -2.105439],
+        [1.019306, 9.027613, -0.280860, -0.162882],
+        [2.81511, 1.597273, -0.366471, 10.300301],
+    ],
+];
+
+/// The taps of every component one after the other, and the weights of every component.
+struct BokehKernel {
+    radius: usize,
+    taps: Vec<[f32; 2]>,
+    weights: Vec<[f32; 2]>,
+}
+
+impl BokehKernel {
+    /// Generates a kernel with `radius` taps on either side of the centre, normalised so that
+    /// the blur keeps the brightness of the image.
+    fn new(radius: usize, component_count: usize) -> BokehKernel {
+        let parameters = COMPONENT_PARAMETERS[component_count - 1];
+
+        let mut taps = Vec::new();
+        let mut weights = Vec::new();
+
+        for &[a, b, weight_real, weight_imaginary] in parameters.iter() {
+            for i in -(radius as i32)..=radius as i32 {
+                let x = i as f32 / radius as f32;
+                let x2 = x * x;
+
+                let magnitude = (-a * x2).exp();
+
+                taps.push([magnitude * (b * x2).cos(), magnitude * (b * x2).sin()]);
+            }
+
+            weights.push([weight_real, weight_imaginary]);
+        }
+
+        // The kernel is applied horizontally and then vertically, so the weighted sum of the
+        // products of every pair of taps has to come out as one.
+        let size = radius * 2 + 1;
+        let mut sum = 0.0;
+
+        for (component, weight) in weights.iter().enumerate() {
+            let component_taps = &taps[component * size..(component + 1) * size];
+
+            for p in component_taps.iter() {
+                for q in component_taps.iter() {
+                    let real = p[0] * q[0] - p[1] * q[1];
+                    let imaginary = p[0] * q[1] + p[1] * q[0];
+
+                    sum += weight[0] * real + weight[1] * imaginary;
+                }
+            }
+        }
+
+        let scale = 1.0 / sum.sqrt();
+
+        for tap in taps.iter_mut() {
+            tap[0] *= scale;
+            tap[1] *= scale;
+        }
+
+        BokehKernel {
+            radius,
+            taps,
+            weights,
+        }
+    }
+
+    fn defines(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("KERNEL_RADIUS", self.radius.to_string()),
+            ("COMPONENT_COUNT", self.weights.len().to_string()),
+        ]
+    }
+}
+
+/// A horizontal pass, convolving one channel with up to two components into its own buffer.
+struct ChannelPass {
+    program: Program,
+    buffer: Texture,
+    // The sampler the vertical pass reads the buffer through.
+    sampler: String,
+}
+
+/// A circular bokeh, like the out of focus background of a photo, and the slowest filter.
+///
+/// The bokeh is a sum of complex Gaussian components, each of which is separable, so every colour
+/// channel is convolved horizontally into its own buffers first and then vertically into the
+/// result.
+pub struct Bokeh {
+    program: Program,
+    kernel: BokehKernel,
+    channel_passes: Vec<ChannelPass>,
+    frame_buffer: FrameBuffer,
+    quad: Quad,
+    resolution: na::Vector2<f32>,
+    filter_radius: f32,
+}
+
+impl Bokeh {
+    /// Creates a blur with a radius in pixels and 1 to `MAX_COMPONENT_COUNT` components, more of
+    /// which give a sharper edged bokeh.
+    pub fn new(
+        gl: &gl::Gl,
+        screen_width: u32,
+        screen_height: u32,
+        format: gl::types::GLenum,
+        radius: f32,
+        component_count: usize,
+    ) -> Result<Bokeh, failure::Error> {
+        let component_count = component_count.clamp(1, MAX_COMPONENT_COUNT);
+
+        // Wide blurs spread their taps apart rather than using more of them.
+        let kernel_radius = (radius.round() as usize).clamp(1, MAX_KERNEL_RADIUS);
+        let filter_radius = radius.max(1.0) / kernel_radius as f32;
+
+        let kernel = BokehKernel::new(kernel_radius, component_count);
+
+        let program = load_program(gl, BOKEH_FRAG, &kernel.defines(), "bokeh")?;
+
+        let mut channel_passes = Vec::new();
+
+        for (channel, name) in [("r", "R"), ("g", "G"), ("b", "B")].iter() {
+            for first_component in (0..component_count).step_by(2) {
+                let mut defines = kernel.defines();
+                defines.push(("CHANNEL", channel.to_string()));
+                defines.push(("FIRST_COMPONENT", first_component.to_string()));
+
+                let sampler = format!("Texture{}{}", name, first_component / 2);
+
+                channel_passes.push(ChannelPass {
+                    program: load_program(gl, BOKEH_PASS_FRAG, &defines, &sampler)?,
+                    buffer: Texture::new_with_format(gl, screen_width, screen_height, format)?,
+                    sampler,
+                });
+            }
+        }
+
+        Ok(Bokeh {
+            program,
+            kernel,
+            channel_passes,
+            frame_buffer: FrameBuffer::new(gl),
+            quad: full_screen_quad(gl),
+            resolution: na::Vector2::new(screen_width as f32, screen_height as f32),
+            filter_radius,
+        })
+    }
+
+    fn set_kernel_uniforms(&self, program: &Program) {
+        if let Some(loc) = program.get_uniform_location("Resolution") {
+            program.set_uniform_2f(loc, &self.resolution);
+        }
+
+        if let Some(loc) = program.get_uniform_location("FilterRadius") {
+            program.set_uniform_1f(loc, self.filter_radius)
+        }
+
+        if let Some(loc) = program.get_uniform_location("Kernels") {
+            program.set_uniform_2fv(loc, &self.kernel.taps)
+        }
+
+        if let Some(loc) = program.get_uniform_location("Weights") {
+            program.set_uniform_2fv(loc, &self.kernel.weights)
+        }
+    }
+}
+
+impl BlurFilter for Bokeh {
+    fn apply(&self, gl: &gl::Gl, source: &Texture, target: &Texture) {
+        for pass in self.channel_passes.iter() {
+            pass.program.set_used();
+
+            bind_texture(&pass.program, "Texture", source, 0);
+            self.set_kernel_uniforms(&pass.program);
+
+            draw(gl, &self.quad, &self.frame_buffer, &pass.buffer);
+        }
+
+        self.program.set_used();
+
+        for (i, pass) in self.channel_passes.iter().enumerate() {
+            bind_texture(&self.program, &pass.sampler, &pass.buffer, i as u32);
+        }
+
+        self.set_kernel_uniforms(&self.program);
+
+        draw(gl, &self.quad, &self.frame_buffer, target);
+    }
+}
@@ -0,0 +1,91 @@
+﻿use super::{bind_texture, draw, full_screen_quad, load_program, BlurFilter};
+use crate::quad::Quad;
+use crate::render_gl::{FrameBuffer, Program, Texture};
+use nalgebra as na;
+
+const GAUSSIAN_FRAG: &str = include_str!("../../assets/shaders/gaussian.frag");
+
+const MAX_KERNEL_RADIUS: usize = 24;
+
+// Standard deviation of the Gaussian, relative to the radius of the blur.
+const SIGMA: f32 = 0.5;
+
+/// A separable Gaussian, blurring horizontally into a buffer and then vertically into the result.
+pub struct Gaussian {
+    program: Program,
+    weights: Vec<f32>,
+    buffer: Texture,
+    frame_buffer: FrameBuffer,
+    quad: Quad,
+    // The distance between two taps, in texture coordinates.
+    step: na::Vector2<f32>,
+}
+
+impl Gaussian {
+    pub fn new(
+        gl: &gl::Gl,
+        screen_width: u32,
+        screen_height: u32,
+        format: gl::types::GLenum,
+        radius: f32,
+    ) -> Result<Gaussian, failure::Error> {
+        // Wide blurs spread their taps apart rather than using more of them.
+        let kernel_radius = (radius.round() as usize).clamp(1, MAX_KERNEL_RADIUS);
+        let filter_radius = radius.max(1.0) / kernel_radius as f32;
+
+        let sigma = kernel_radius as f32 * SIGMA;
+
+        let mut weights: Vec<f32> = (0..=kernel_radius)
+            .map(|i| (-((i * i) as f32) / (2.0 * sigma * sigma)).exp())
+            .collect();
+
+        // Every weight but the centre one is used on both sides.
+        let sum = weights.iter().sum::<f32>() * 2.0 - weights[0];
+
+        for weight in weights.iter_mut() {
+            *weight /= sum;
+        }
+
+        let program = load_program(
+            gl,
+            GAUSSIAN_FRAG,
+            &[("KERNEL_RADIUS", kernel_radius.to_string())],
+            "gaussian",
+        )?;
+
+        Ok(Gaussian {
+            program,
+            weights,
+            buffer: Texture::new_with_format(gl, screen_width, screen_height, format)?,
+            frame_buffer: FrameBuffer::new(gl),
+            quad: full_screen_quad(gl),
+            step: na::Vector2::new(
+                filter_radius / screen_width as f32,
+                filter_radius / screen_height as f32,
+            ),
+        })
+    }
+
+    fn pass(&self, gl: &gl::Gl, source: &Texture, target: &Texture, step: na::Vector2<f32>) {
+        bind_texture(&self.program, "Texture", source, 0);
+
+        if let Some(loc) = self.program.get_uniform_location("Step") {
+            self.program.set_uniform_2f(loc, &step);
+        }
+
+        draw(gl, &self.quad, &self.frame_buffer, target);
+    }
+}
+
+impl BlurFilter for Gaussian {
+    fn apply(&self, gl: &gl::Gl, source: &Texture, target: &Texture) {
+        self.program.set_used();
+
+        if let Some(loc) = self.program.get_uniform_location("Weights") {
+            self.program.set_uniform_1fv(loc, &self.weights);
+        }
+
+        self.pass(gl, source, &self.buffer, na::Vector2::new(self.step.x, 0.0));
+        self.pass(gl, &self.buffer, target, na::Vector2::new(0.0, self.step.y));
+    }
+}
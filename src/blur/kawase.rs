@@ -0,0 +1,105 @@
+﻿use super::{bind_texture, draw, full_screen_quad, load_program, BlurFilter};
+use crate::quad::Quad;
+use crate::render_gl::{FrameBuffer, Program, Texture};
+use nalgebra as na;
+
+const KAWASE_DOWN_FRAG: &str = include_str!("../../assets/shaders/kawase_down.frag");
+const KAWASE_UP_FRAG: &str = include_str!("../../assets/shaders/kawase_up.frag");
+
+const MAX_ITERATIONS: usize = 8;
+
+/// The dual Kawase blur, which samples the background down through buffers of half the size
+/// each and back up again, blurring a little at every step. Cheap even for wide blurs.
+pub struct Kawase {
+    down_program: Program,
+    up_program: Program,
+    // From half the size of the screen down.
+    buffers: Vec<Texture>,
+    frame_buffer: FrameBuffer,
+    quad: Quad,
+    // How far apart the taps are, in pixels of the buffers.
+    offset: f32,
+}
+
+impl Kawase {
+    pub fn new(
+        gl: &gl::Gl,
+        screen_width: u32,
+        screen_height: u32,
+        format: gl::types::GLenum,
+        radius: f32,
+    ) -> Result<Kawase, failure::Error> {
+        // Every iteration roughly doubles the radius, the offset makes up the difference.
+        let iterations = (radius.max(1.0).log2().round() as usize).clamp(1, MAX_ITERATIONS);
+        let offset = radius.max(1.0) / (1 << iterations) as f32;
+
+        let mut buffers = Vec::new();
+
+        for i in 1..=iterations {
+            let buffer = Texture::new_with_format(
+                gl,
+                (screen_width >> i).max(1),
+                (screen_height >> i).max(1),
+                format,
+            )?;
+
+            buffer.set_linear_filtering();
+
+            buffers.push(buffer);
+        }
+
+        Ok(Kawase {
+            down_program: load_program(gl, KAWASE_DOWN_FRAG, &[], "kawase_down")?,
+            up_program: load_program(gl, KAWASE_UP_FRAG, &[], "kawase_up")?,
+            buffers,
+            frame_buffer: FrameBuffer::new(gl),
+            quad: full_screen_quad(gl),
+            offset,
+        })
+    }
+
+    fn pass(&self, gl: &gl::Gl, program: &Program, source: &Texture, target: &Texture) {
+        let (width, height) = target.dimensions();
+
+        bind_texture(program, "Texture", source, 0);
+
+        if let Some(loc) = program.get_uniform_location("HalfPixel") {
+            program.set_uniform_2f(
+                loc,
+                &na::Vector2::new(
+                    0.5 * self.offset / width as f32,
+                    0.5 * self.offset / height as f32,
+                ),
+            );
+        }
+
+        draw(gl, &self.quad, &self.frame_buffer, target);
+    }
+}
+
+impl BlurFilter for Kawase {
+    fn apply(&self, gl: &gl::Gl, source: &Texture, target: &Texture) {
+        // The taps fall in between pixels, to average four of them each.
+        source.set_linear_filtering();
+
+        self.down_program.set_used();
+
+        let mut input = source;
+
+        for buffer in self.buffers.iter() {
+            self.pass(gl, &self.down_program, input, buffer);
+
+            input = buffer;
+        }
+
+        self.up_program.set_used();
+
+        for buffer in self.buffers.iter().rev().skip(1) {
+            self.pass(gl, &self.up_program, input, buffer);
+
+            input = buffer;
+        }
+
+        self.pass(gl, &self.up_program, input, target);
+    }
+}
@@ -0,0 +1,59 @@
+﻿use super::{bind_texture, draw, full_screen_quad, load_program, BlurFilter};
+use crate::quad::Quad;
+use crate::render_gl::{FrameBuffer, Program, Texture};
+
+const QUAD_FRAG: &str = include_str!("../../assets/shaders/quad.frag");
+const MIPMAP_FRAG: &str = include_str!("../../assets/shaders/mipmap.frag");
+
+/// The cheapest filter, which copies the background into a texture of the size of the screen,
+/// has the driver shrink it into mipmaps and samples a smaller one. Blocky up close.
+pub struct Mipmap {
+    copy_program: Program,
+    program: Program,
+    levels: Texture,
+    frame_buffer: FrameBuffer,
+    quad: Quad,
+    lod: f32,
+}
+
+impl Mipmap {
+    pub fn new(
+        gl: &gl::Gl,
+        screen_width: u32,
+        screen_height: u32,
+        format: gl::types::GLenum,
+        radius: f32,
+    ) -> Result<Mipmap, failure::Error> {
+        Ok(Mipmap {
+            copy_program: load_program(gl, QUAD_FRAG, &[], "mipmap_copy")?,
+            program: load_program(gl, MIPMAP_FRAG, &[], "mipmap")?,
+            levels: Texture::new_with_format(gl, screen_width, screen_height, format)?,
+            frame_buffer: FrameBuffer::new(gl),
+            quad: full_screen_quad(gl),
+            // Every level halves the size, so the pixels of level n are 2^n pixels wide.
+            lod: radius.max(1.0).log2(),
+        })
+    }
+}
+
+impl BlurFilter for Mipmap {
+    fn apply(&self, gl: &gl::Gl, source: &Texture, target: &Texture) {
+        self.copy_program.set_used();
+
+        bind_texture(&self.copy_program, "Texture", source, 0);
+
+        draw(gl, &self.quad, &self.frame_buffer, &self.levels);
+
+        self.levels.generate_mipmaps();
+
+        self.program.set_used();
+
+        bind_texture(&self.program, "Texture", &self.levels, 0);
+
+        if let Some(loc) = self.program.get_uniform_location("Lod") {
+            self.program.set_uniform_1f(loc, self.lod);
+        }
+
+        draw(gl, &self.quad, &self.frame_buffer, target);
+    }
+}
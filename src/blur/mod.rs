@@ -0,0 +1,118 @@
+﻿mod bokeh;
+mod gaussian;
+mod kawase;
+mod mipmap;
+
+pub use self::bokeh::Bokeh;
+pub use self::gaussian::Gaussian;
+pub use self::kawase::Kawase;
+pub use self::mipmap::Mipmap;
+
+use crate::config::Blur;
+use crate::quad::Quad;
+use crate::render_gl::{with_defines, Error, FrameBuffer, Program, Shader, Texture};
+
+const BLUR_VERT: &str = include_str!("../../assets/shaders/blur.vert");
+
+/// A way of blurring the background seen through the fog.
+///
+/// Filters own the programs and intermediate buffers they need, sized for the screen they were
+/// created for. They expect blending to be disabled.
+pub trait BlurFilter {
+    /// Blurs `source`, stretched over the screen, into `target`, which has the size of the screen.
+    fn apply(&self, gl: &gl::Gl, source: &Texture, target: &Texture);
+}
+
+/// Creates the filter of the given kind, blurring by roughly `radius` pixels into buffers of the
+/// given texture format. Only the bokeh uses `component_count`.
+pub fn new_filter(
+    gl: &gl::Gl,
+    blur: Blur,
+    screen_width: u32,
+    screen_height: u32,
+    format: gl::types::GLenum,
+    radius: f32,
+    component_count: usize,
+) -> Result<Box<dyn BlurFilter>, failure::Error> {
+    Ok(match blur {
+        Blur::Bokeh => Box::new(Bokeh::new(
+            gl,
+            screen_width,
+            screen_height,
+            format,
+            radius,
+            component_count,
+        )?),
+        Blur::Gaussian => Box::new(Gaussian::new(
+            gl,
+            screen_width,
+            screen_height,
+            format,
+            radius,
+        )?),
+        Blur::Kawase => Box::new(Kawase::new(
+            gl,
+            screen_width,
+            screen_height,
+            format,
+            radius,
+        )?),
+        Blur::Mipmap => Box::new(Mipmap::new(
+            gl,
+            screen_width,
+            screen_height,
+            format,
+            radius,
+        )?),
+    })
+}
+
+/// A quad covering the whole target of a pass.
+pub fn full_screen_quad(gl: &gl::Gl) -> Quad {
+    Quad::new_with_size(gl, -1.0, -1.0, 1.0, 1.0)
+}
+
+pub fn load_program(
+    gl: &gl::Gl,
+    frag_source: &str,
+    defines: &[(&str, String)],
+    debug_name: &str,
+) -> Result<Program, failure::Error> {
+    let program = Program::from_shaders(
+        gl,
+        &[
+            Shader::from_vert_source_str(gl, BLUR_VERT)?,
+            Shader::from_frag_source_str(gl, &with_defines(frag_source, defines))?,
+        ],
+    )
+    .map_err(|msg| Error::LinkError {
+        message: msg,
+        name: debug_name.to_string(),
+    })?;
+
+    Ok(program)
+}
+
+/// Binds `texture` to the sampler of the given name, if the program uses it.
+pub fn bind_texture(program: &Program, name: &str, texture: &Texture, unit: u32) {
+    if let Some(loc) = program.get_uniform_location(name) {
+        texture.bind_at(unit);
+        program.set_uniform_1i(loc, unit as i32);
+    }
+}
+
+/// Draws the quad into all of `target` with the program in use.
+pub fn draw(gl: &gl::Gl, quad: &Quad, frame_buffer: &FrameBuffer, target: &Texture) {
+    let (width, height) = target.dimensions();
+
+    frame_buffer.bind();
+    frame_buffer.attach_texture(target);
+
+    unsafe {
+        gl.Viewport(0, 0, width as i32, height as i32);
+    }
+
+    quad.render(gl);
+
+    frame_buffer.unbind();
+}
@@ -0,0 +1,167 @@
+﻿use crate::render_gl::buffer::ArrayBuffer;
+use crate::vertex::DropletInstance;
+use nalgebra::Vector2;
+use rand::prelude::*;
+use std::time::Duration;
+
+const CELL_SIZE: f32 = 32.0;
+
+// Seconds a wiped micro-droplet stays clear before it starts condensing again.
+const REGROW_DELAY_RANGE: (f32, f32) = (5.0, 30.0);
+// How fast a condensing micro-droplet grows back, in pixels of diameter per second.
+const REGROW_RATE: f32 = 0.25;
+
+struct MicroDroplet {
+    pos: Vector2<f32>,
+    size: f32,
+    max_size: f32,
+    regrow_delay: f32,
+}
+
+/// A static layer of tiny condensation beads.
+///
+/// These never move or merge, so they are kept out of `Droplets` and the collision world and
+/// are only bucketed into a grid, to let moving droplets wipe them cheaply.
+pub struct Condensation {
+    micro_droplets: Vec<MicroDroplet>,
+
+    grid: Vec<Vec<usize>>,
+    columns: usize,
+    rows: usize,
+
+    instance_vbo: ArrayBuffer,
+    instance_count: usize,
+    dirty: bool,
+}
+
+impl Condensation {
+    pub fn new(
+        gl: &gl::Gl,
+        count: usize,
+        size_range: (f32, f32),
+        width: u32,
+        height: u32,
+    ) -> Condensation {
+        let mut rng = rand::thread_rng();
+
+        let columns = (width as f32 / CELL_SIZE).ceil() as usize + 1;
+        let rows = (height as f32 / CELL_SIZE).ceil() as usize + 1;
+
+        let mut grid = vec![Vec::new(); columns * rows];
+
+        let micro_droplets = (0..count)
+            .map(|i| {
+                let pos = Vector2::new(
+                    rng.gen_range(0.0, width as f32),
+                    rng.gen_range(0.0, height as f32),
+                );
+
+                let column = (pos.x / CELL_SIZE) as usize;
+                let row = (pos.y / CELL_SIZE) as usize;
+
+                grid[row * columns + column].push(i);
+
+                let max_size = rng.gen_range(size_range.0, size_range.1);
+
+                MicroDroplet {
+                    pos,
+                    size: max_size,
+                    max_size,
+                    regrow_delay: 0.0,
+                }
+            })
+            .collect();
+
+        Condensation {
+            micro_droplets,
+            grid,
+            columns,
+            rows,
+            instance_vbo: ArrayBuffer::new(gl),
+            instance_count: 0,
+            dirty: true,
+        }
+    }
+
+    /// Clears every micro-droplet touched by a droplet of the given radius.
+    pub fn wipe(&mut self, rng: &mut ThreadRng, center: &Vector2<f32>, radius: f32) {
+        let min_column = ((center.x - radius) / CELL_SIZE).floor().max(0.0) as usize;
+        let min_row = ((center.y - radius) / CELL_SIZE).floor().max(0.0) as usize;
+        let max_column =
+            (((center.x + radius) / CELL_SIZE).floor().max(0.0) as usize).min(self.columns - 1);
+        let max_row =
+            (((center.y + radius) / CELL_SIZE).floor().max(0.0) as usize).min(self.rows - 1);
+
+        for row in min_row..=max_row {
+            for column in min_column..=max_column {
+                for &i in self.grid[row * self.columns + column].iter() {
+                    let micro_droplet = &mut self.micro_droplets[i];
+
+                    if micro_droplet.size <= 0.0 {
+                        continue;
+                    }
+
+                    let reach = radius + micro_droplet.size * 0.5;
+
+                    if (micro_droplet.pos - center).norm_squared() < reach * reach {
+                        micro_droplet.size = 0.0;
+                        micro_droplet.regrow_delay =
+                            rng.gen_range(REGROW_DELAY_RANGE.0, REGROW_DELAY_RANGE.1);
+
+                        self.dirty = true;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Lets wiped micro-droplets condense back and uploads the layer if anything changed.
+    pub fn update(&mut self, dt: &Duration) {
+        let dt = dt.as_secs_f32();
+
+        for micro_droplet in self.micro_droplets.iter_mut() {
+            if micro_droplet.size >= micro_droplet.max_size {
+                continue;
+            }
+
+            if micro_droplet.regrow_delay > 0.0 {
+                micro_droplet.regrow_delay -= dt;
+                continue;
+            }
+
+            micro_droplet.size =
+                (micro_droplet.size + REGROW_RATE * dt).min(micro_droplet.max_size);
+
+            self.dirty = true;
+        }
+
+        if !self.dirty {
+            return;
+        }
+
+        let instances: Vec<DropletInstance> = self
+            .micro_droplets
+            .iter()
+            .filter(|d| d.size > 0.0)
+            .map(|d| DropletInstance {
+                offset: (d.pos.x, d.pos.y, d.size).into(),
+                wobble: (0.0, 0.0).into(),
+            })
+            .collect();
+
+        self.instance_vbo.bind();
+        self.instance_vbo.dynamic_draw_data(&instances);
+        self.instance_vbo.unbind();
+
+        self.instance_count = instances.len();
+        self.dirty = false;
+    }
+
+    pub fn instance_buffer(&self) -> &ArrayBuffer {
+        &self.instance_vbo
+    }
+
+    pub fn instance_count(&self) -> usize {
+        self.instance_count
+    }
+}
@@ -1,6 +1,192 @@
 ﻿use std::path::{Path, PathBuf};
 
 const BACKGROUND_KEY: &str = "background";
+const FOG_HALF_LIFE_KEY: &str = "fog_half_life";
+const START_KEY: &str = "start";
+const SIMULATION_KEY: &str = "simulation";
+const GPU_DROPLET_COUNT_KEY: &str = "gpu_droplet_count";
+const WARM_UP_KEY: &str = "warm_up";
+const WARM_UP_DROPLETS_KEY: &str = "warm_up_droplets";
+const WARM_UP_SECONDS_KEY: &str = "warm_up_seconds";
+const PRECIPITATION_KEY: &str = "precipitation";
+const FROST_KEY: &str = "frost";
+const WETTING_MASK_KEY: &str = "wetting_mask";
+const WETTING_MASK_MODE_KEY: &str = "wetting_mask_mode";
+const BACKGROUND_FIT_KEY: &str = "background_fit";
+const BACKGROUND_FOCUS_KEY: &str = "background_focus";
+const LETTERBOX_COLOR_KEY: &str = "letterbox_color";
+const SRGB_KEY: &str = "srgb";
+const PROCEDURAL_BACKGROUND_KEY: &str = "procedural_background";
+const CITY_PALETTE_KEY: &str = "city_palette";
+const CITY_DENSITY_KEY: &str = "city_density";
+const CITY_SEED_KEY: &str = "city_seed";
+const GRADIENT_TOP_KEY: &str = "gradient_top";
+const GRADIENT_BOTTOM_KEY: &str = "gradient_bottom";
+const SOLID_COLOR_KEY: &str = "solid_color";
+const SLIDESHOW_INTERVAL_KEY: &str = "slideshow_interval";
+const SLIDESHOW_ORDER_KEY: &str = "slideshow_order";
+const CROSSFADE_KEY: &str = "crossfade";
+const ANIMATION_FRAME_RATE_KEY: &str = "animation_frame_rate";
+const ANIMATION_BLUR_INTERVAL_KEY: &str = "animation_blur_interval";
+const BLUR_KEY: &str = "blur";
+const BLUR_RADIUS_KEY: &str = "blur_radius";
+const BLUR_COMPONENTS_KEY: &str = "blur_components";
+
+// Roughly the fade rate of the original per-frame re-fog, 0.25 per second.
+const DEFAULT_FOG_HALF_LIFE: f32 = 2.77;
+
+const DEFAULT_GPU_DROPLET_COUNT: usize = 100_000;
+
+const DEFAULT_CITY_SEED: u64 = 1;
+// A dusky blue fading into a dark purple.
+const DEFAULT_GRADIENT_TOP: (f32, f32, f32) = (0.16, 0.22, 0.35);
+const DEFAULT_GRADIENT_BOTTOM: (f32, f32, f32) = (0.05, 0.03, 0.08);
+
+const DEFAULT_SLIDESHOW_INTERVAL: f32 = 300.0;
+const DEFAULT_CROSSFADE: f32 = 3.0;
+
+const DEFAULT_ANIMATION_FRAME_RATE: f32 = 24.0;
+
+const DEFAULT_BLUR_RADIUS: f32 = 8.0;
+const DEFAULT_BLUR_COMPONENTS: usize = 2;
+
+const DEFAULT_WARM_UP_DROPLETS: usize = 800;
+const DEFAULT_WARM_UP_SECONDS: f32 = 30.0;
+
+/// What the glass looks like when the screensaver starts.
+pub enum StartState {
+    /// A clean, fogged glass.
+    Empty,
+    /// The state saved when the screensaver last exited, or `Rainy` if there is none.
+    Resume,
+    /// The "already rainy" snapshot shipped in the assets.
+    Rainy,
+}
+
+/// How an empty glass is made to look rainy before the first frame.
+pub enum WarmUp {
+    None,
+    /// Scatters this many static droplets over the glass.
+    Scatter(usize),
+    /// Runs the simulation for this many seconds.
+    FastForward(f32),
+}
+
+/// Where the droplets are simulated.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Simulation {
+    Cpu,
+    /// Compute shaders, which need OpenGL 4.3. Falls back to `Cpu` when it is not available.
+    Gpu,
+}
+
+/// How the background image is laid out on screens of a different shape or size.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum BackgroundFit {
+    /// Covers the whole screen, cropping the image around its focus.
+    Fill,
+    /// Shows the whole image, with bars of the letterbox colour on the sides it does not reach.
+    Fit,
+    /// Covers the whole screen, distorting the image.
+    Stretch,
+    /// Shows the image at its own size in the middle of the screen, surrounded by the letterbox
+    /// colour or cropped around its focus.
+    Center,
+    /// Repeats the image at its own size over the screen.
+    Tile,
+}
+
+/// A background generated rather than loaded from a file.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ProceduralBackground {
+    /// Out of focus city lights at night. Density scales the number of lights, and the same
+    /// seed always gives the same lights.
+    City {
+        palette: Palette,
+        density: f32,
+        seed: u64,
+    },
+    /// A vertical gradient between two colours.
+    Gradient {
+        top: (f32, f32, f32),
+        bottom: (f32, f32, f32),
+    },
+    Solid((f32, f32, f32)),
+}
+
+/// The colours of the city lights.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Palette {
+    /// Orange street lights, white headlights and red tail lights.
+    Sodium,
+    /// Pink, cyan and violet signs.
+    Neon,
+    /// Cold blue and white lights.
+    Ice,
+}
+
+/// The order a slideshow goes through its images in.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SlideshowOrder {
+    /// A random order, shuffled again every time the slideshow starts over.
+    Shuffled,
+    /// Sorted by file name.
+    Sorted,
+}
+
+/// How the background seen through the fog is blurred.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Blur {
+    /// A circular bokeh, like an out of focus lens. The slowest.
+    Bokeh,
+    /// A separable Gaussian.
+    Gaussian,
+    /// Sampling down through smaller and smaller buffers and back up again, which is cheap.
+    Kawase,
+    /// Sampling a smaller mipmap of the background, which is the cheapest but blocky.
+    Mipmap,
+}
+
+/// What falls onto the glass.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Precipitation {
+    Rain,
+    /// Flakes that stick to the glass and slowly melt into droplets.
+    Snow,
+    /// Small droplets that hit the glass fast and run right away.
+    Sleet,
+    /// Stones that bounce off, leaving a small splash of droplets behind.
+    Hail,
+}
+
+impl Simulation {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Simulation::Cpu => "cpu",
+            Simulation::Gpu => "gpu",
+        }
+    }
+}
+
+impl Precipitation {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Precipitation::Rain => "rain",
+            Precipitation::Snow => "snow",
+            Precipitation::Sleet => "sleet",
+            Precipitation::Hail => "hail",
+        }
+    }
+}
+
+/// How a surface treatment makes water behave on the glass.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Wettability {
+    /// Water repellent, droplets bead up and roll off.
+    Hydrophobic,
+    /// Wets easily, droplets spread out and cling to it.
+    Hydrophilic,
+}
 
 pub struct Config {
     path: String,
@@ -17,27 +203,320 @@ impl Config {
         }
     }
 
-    pub fn background(&self) -> Option<PathBuf> {
+    fn get(&self, key: &str) -> Option<String> {
         let ini = ini::Ini::load_from_file(&self.path);
 
         match ini {
-            Ok(ini) => ini
-                .get_from(None::<&str>, BACKGROUND_KEY)
-                .map(PathBuf::from),
+            Ok(ini) => ini.get_from(None::<&str>, key).map(String::from),
             Err(_) => None,
         }
     }
 
+    pub fn background(&self) -> Option<PathBuf> {
+        self.get(BACKGROUND_KEY).map(PathBuf::from)
+    }
+
+    /// Seconds it takes a wiped area of the glass to get half of its fog back.
+    pub fn fog_half_life(&self) -> f32 {
+        self.get(FOG_HALF_LIFE_KEY)
+            .and_then(|value| value.parse::<f32>().ok())
+            .filter(|value| *value > 0.0)
+            .unwrap_or(DEFAULT_FOG_HALF_LIFE)
+    }
+
+    pub fn start_state(&self) -> StartState {
+        match self.get(START_KEY).as_deref() {
+            Some("empty") => StartState::Empty,
+            Some("rainy") => StartState::Rainy,
+            _ => StartState::Resume,
+        }
+    }
+
+    pub fn warm_up(&self) -> WarmUp {
+        match self.get(WARM_UP_KEY).as_deref() {
+            Some("scatter") => WarmUp::Scatter(
+                self.get(WARM_UP_DROPLETS_KEY)
+                    .and_then(|value| value.parse::<usize>().ok())
+                    .unwrap_or(DEFAULT_WARM_UP_DROPLETS),
+            ),
+            Some("fast_forward") => WarmUp::FastForward(
+                self.get(WARM_UP_SECONDS_KEY)
+                    .and_then(|value| value.parse::<f32>().ok())
+                    .filter(|value| *value >= 0.0)
+                    .unwrap_or(DEFAULT_WARM_UP_SECONDS),
+            ),
+            _ => WarmUp::None,
+        }
+    }
+
+    pub fn simulation(&self) -> Simulation {
+        match self.get(SIMULATION_KEY).as_deref() {
+            Some("gpu") => Simulation::Gpu,
+            _ => Simulation::Cpu,
+        }
+    }
+
+    pub fn precipitation(&self) -> Precipitation {
+        match self.get(PRECIPITATION_KEY).as_deref() {
+            Some("snow") => Precipitation::Snow,
+            Some("sleet") => Precipitation::Sleet,
+            Some("hail") => Precipitation::Hail,
+            _ => Precipitation::Rain,
+        }
+    }
+
+    /// Whether ice crystals grow over the glass.
+    pub fn frost(&self) -> bool {
+        matches!(self.get(FROST_KEY).as_deref(), Some("true"))
+    }
+
+    /// Whether colours are blended and blurred in linear space, decoding backgrounds from sRGB
+    /// and encoding the screen to it.
+    pub fn srgb(&self) -> bool {
+        matches!(self.get(SRGB_KEY).as_deref(), Some("true"))
+    }
+
+    pub fn background_fit(&self) -> BackgroundFit {
+        match self.get(BACKGROUND_FIT_KEY).as_deref() {
+            Some("fit") => BackgroundFit::Fit,
+            Some("stretch") => BackgroundFit::Stretch,
+            Some("center") => BackgroundFit::Center,
+            Some("tile") => BackgroundFit::Tile,
+            _ => BackgroundFit::Fill,
+        }
+    }
+
+    /// The point of the background image that cropping keeps in view, as `x,y` fractions of its
+    /// width and height from the top left corner. The centre by default.
+    pub fn background_focus(&self) -> (f32, f32) {
+        self.get(BACKGROUND_FOCUS_KEY)
+            .and_then(|value| {
+                let mut coordinates = value
+                    .split(',')
+                    .map(|coordinate| coordinate.trim().parse::<f32>().ok());
+
+                match (coordinates.next(), coordinates.next(), coordinates.next()) {
+                    (Some(Some(x)), Some(Some(y)), None) => {
+                        Some((x.clamp(0.0, 1.0), y.clamp(0.0, 1.0)))
+                    }
+                    _ => None,
+                }
+            })
+            .unwrap_or((0.5, 0.5))
+    }
+
+    /// The colour around a background image that does not cover the screen, from a `#rrggbb`
+    /// value. Black by default.
+    pub fn letterbox_color(&self) -> (f32, f32, f32) {
+        self.color(LETTERBOX_COLOR_KEY).unwrap_or((0.0, 0.0, 0.0))
+    }
+
+    /// A background to generate instead of loading an image, if one is chosen.
+    pub fn procedural_background(&self) -> Option<ProceduralBackground> {
+        match self.get(PROCEDURAL_BACKGROUND_KEY).as_deref() {
+            Some("city") => Some(self.city_lights()),
+            Some("gradient") => Some(ProceduralBackground::Gradient {
+                top: self.color(GRADIENT_TOP_KEY).unwrap_or(DEFAULT_GRADIENT_TOP),
+                bottom: self
+                    .color(GRADIENT_BOTTOM_KEY)
+                    .unwrap_or(DEFAULT_GRADIENT_BOTTOM),
+            }),
+            Some("solid") => Some(ProceduralBackground::Solid(
+                self.color(SOLID_COLOR_KEY).unwrap_or(DEFAULT_GRADIENT_TOP),
+            )),
+            _ => None,
+        }
+    }
+
+    /// The background generated when no image loads, which is the chosen procedural background
+    /// or else city lights.
+    pub fn fallback_background(&self) -> ProceduralBackground {
+        self.procedural_background()
+            .unwrap_or_else(|| self.city_lights())
+    }
+
+    fn city_lights(&self) -> ProceduralBackground {
+        let palette = match self.get(CITY_PALETTE_KEY).as_deref() {
+            Some("neon") => Palette::Neon,
+            Some("ice") => Palette::Ice,
+            _ => Palette::Sodium,
+        };
+
+        ProceduralBackground::City {
+            palette,
+            density: self
+                .get(CITY_DENSITY_KEY)
+                .and_then(|value| value.parse::<f32>().ok())
+                .filter(|value| *value > 0.0)
+                .unwrap_or(1.0),
+            seed: self
+                .get(CITY_SEED_KEY)
+                .and_then(|value| value.parse::<u64>().ok())
+                .unwrap_or(DEFAULT_CITY_SEED),
+        }
+    }
+
+    /// Parses a `#rrggbb` value.
+    fn color(&self, key: &str) -> Option<(f32, f32, f32)> {
+        let value = self.get(key)?;
+        let hex = value.trim().trim_start_matches('#');
+
+        if hex.len() != 6 {
+            return None;
+        }
+
+        let channel = |i: usize| {
+            u8::from_str_radix(hex.get(i..i + 2)?, 16)
+                .ok()
+                .map(|c| c as f32 / 255.0)
+        };
+
+        Some((channel(0)?, channel(2)?, channel(4)?))
+    }
+
+    /// Seconds every image of a slideshow is shown for.
+    pub fn slideshow_interval(&self) -> f32 {
+        self.get(SLIDESHOW_INTERVAL_KEY)
+            .and_then(|value| value.parse::<f32>().ok())
+            .filter(|value| *value > 0.0)
+            .unwrap_or(DEFAULT_SLIDESHOW_INTERVAL)
+    }
+
+    pub fn slideshow_order(&self) -> SlideshowOrder {
+        match self.get(SLIDESHOW_ORDER_KEY).as_deref() {
+            Some("sorted") => SlideshowOrder::Sorted,
+            _ => SlideshowOrder::Shuffled,
+        }
+    }
+
+    /// Seconds it takes one background to fade into the next.
+    pub fn crossfade(&self) -> f32 {
+        self.get(CROSSFADE_KEY)
+            .and_then(|value| value.parse::<f32>().ok())
+            .filter(|value| *value >= 0.0)
+            .unwrap_or(DEFAULT_CROSSFADE)
+    }
+
+    /// Frames per second of a numbered frame sequence. GIF files bring their own timing.
+    pub fn animation_frame_rate(&self) -> f32 {
+        self.get(ANIMATION_FRAME_RATE_KEY)
+            .and_then(|value| value.parse::<f32>().ok())
+            .filter(|value| *value > 0.0)
+            .unwrap_or(DEFAULT_ANIMATION_FRAME_RATE)
+    }
+
+    /// Every how many frames of an animated background the blurred background seen through the
+    /// fog is updated. Blurring is slow, so slower machines may only keep up with a few.
+    pub fn animation_blur_interval(&self) -> usize {
+        self.get(ANIMATION_BLUR_INTERVAL_KEY)
+            .and_then(|value| value.parse::<usize>().ok())
+            .filter(|value| *value > 0)
+            .unwrap_or(1)
+    }
+
+    pub fn blur(&self) -> Blur {
+        match self.get(BLUR_KEY).as_deref() {
+            Some("gaussian") => Blur::Gaussian,
+            Some("kawase") => Blur::Kawase,
+            Some("mipmap") => Blur::Mipmap,
+            _ => Blur::Bokeh,
+        }
+    }
+
+    /// Radius of the blur seen through the fog, in pixels.
+    pub fn blur_radius(&self) -> f32 {
+        self.get(BLUR_RADIUS_KEY)
+            .and_then(|value| value.parse::<f32>().ok())
+            .filter(|value| *value >= 1.0)
+            .unwrap_or(DEFAULT_BLUR_RADIUS)
+    }
+
+    /// Number of components the bokeh of the blur is made of, from 1 to 3. More components give
+    /// the bokeh sharper edges but take longer to render. Only the `Bokeh` blur uses them.
+    pub fn blur_components(&self) -> usize {
+        self.get(BLUR_COMPONENTS_KEY)
+            .and_then(|value| value.parse::<usize>().ok())
+            .filter(|value| (1..=3).contains(value))
+            .unwrap_or(DEFAULT_BLUR_COMPONENTS)
+    }
+
+    /// An image whose bright parts are treated to make the glass behave differently, along with
+    /// the treatment. A relative path is relative to the screensaver.
+    pub fn wetting_mask(&self) -> Option<(PathBuf, Wettability)> {
+        let wettability = match self.get(WETTING_MASK_MODE_KEY).as_deref() {
+            Some("hydrophilic") => Wettability::Hydrophilic,
+            _ => Wettability::Hydrophobic,
+        };
+
+        self.get(WETTING_MASK_KEY).map(|path| {
+            let directory = std::env::current_exe().unwrap();
+            let directory = directory.parent().unwrap();
+
+            // Joining an absolute path replaces the directory.
+            (directory.join(path), wettability)
+        })
+    }
+
+    /// How many droplets the GPU simulation makes room for.
+    pub fn gpu_droplet_count(&self) -> usize {
+        self.get(GPU_DROPLET_COUNT_KEY)
+            .and_then(|value| value.parse::<usize>().ok())
+            .filter(|value| *value > 0)
+            .unwrap_or(DEFAULT_GPU_DROPLET_COUNT)
+    }
+
+    /// Paths of the simulation state and fog mask saved on exit.
+    pub fn saved_state(&self) -> (PathBuf, PathBuf) {
+        let exe = std::env::current_exe().unwrap();
+
+        (exe.with_extension("state"), exe.with_extension("mask.png"))
+    }
+
+    /// Path of the small copy of the last background, shown while the background loads.
+    pub fn background_thumbnail(&self) -> PathBuf {
+        std::env::current_exe()
+            .unwrap()
+            .with_extension("thumbnail.png")
+    }
+
+    /// Paths of the simulation state and fog mask shipped with the screensaver.
+    pub fn rainy_state(&self) -> (PathBuf, PathBuf) {
+        let directory = std::env::current_exe()
+            .unwrap()
+            .parent()
+            .unwrap()
+            .join("assets\\state");
+
+        (
+            directory.join("rainy.state"),
+            directory.join("rainy_mask.png"),
+        )
+    }
+
     pub fn cached_background(&self) -> Option<PathBuf> {
         self.background()
             .map(|p| self.backgrounds_directory().join(p.file_name().unwrap()))
     }
 
+    /// Sets the background, keeping every other setting in the file.
     pub fn set_background(&self, filename: &Path) -> std::io::Result<()> {
-        let mut ini = ini::Ini::new();
+        let filename = filename.to_str().ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("{} is not valid Unicode", filename.to_string_lossy()),
+            )
+        })?;
+
+        let mut ini = match ini::Ini::load_from_file(&self.path) {
+            Ok(ini) => ini,
+            Err(ini::Error::Io(e)) if e.kind() == std::io::ErrorKind::NotFound => ini::Ini::new(),
+            Err(ini::Error::Io(e)) => return Err(e),
+            Err(ini::Error::Parse(e)) => {
+                return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+            }
+        };
 
-        ini.with_general_section()
-            .set(BACKGROUND_KEY, filename.to_str().unwrap());
+        ini.with_general_section().set(BACKGROUND_KEY, filename);
 
         ini.write_to_file(&self.path)
     }
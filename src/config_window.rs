@@ -11,7 +11,6 @@ use nwg::NativeUi;
 use std::cell::RefCell;
 use std::env;
 use std::path::{Path, PathBuf};
-use winapi::um::winuser::{SM_CXSCREEN, SM_CYSCREEN};
 
 #[derive(Default, NwgUi)]
 pub struct ConfigWindow {
@@ -128,14 +127,12 @@ impl ConfigWindow {
     }
 
     fn save(&self, path: &Path) -> std::io::Result<()> {
-        use winapi::um::winuser::GetSystemMetrics;
-
         let config = Config::default();
 
         // Remove previous cache.
         if let Some(previous_background) = config.cached_background() {
             if let Err(e) = std::fs::remove_file(&previous_background) {
-                eprintln!(
+                println!(
                     "Failed to delete file {}: {}",
                     previous_background.to_string_lossy(),
                     e.to_string()
@@ -143,17 +140,14 @@ impl ConfigWindow {
             }
         }
 
-        let _ = config.set_background(path);
-
-        let image = image::open(path).unwrap();
-
-        let width = unsafe { GetSystemMetrics(SM_CXSCREEN) };
-        let height = unsafe { GetSystemMetrics(SM_CYSCREEN) };
+        // The thumbnail of the previous background is no placeholder for the new one.
+        let _ = std::fs::remove_file(config.background_thumbnail());
 
-        let resized_image =
-            image.resize_to_fill(width as u32, height as u32, image::FilterType::Gaussian);
+        config.set_background(path)?;
 
-        resized_image.save(config.cached_background().unwrap().as_path())
+        // The screensaver lays the image out on every screen itself, see `BackgroundFit`, and
+        // copying the file keeps animations intact.
+        std::fs::copy(path, config.cached_background().unwrap()).map(|_| ())
     }
 
     fn open(&self) {
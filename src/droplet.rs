@@ -1,6 +1,7 @@
 ﻿use nalgebra::Vector2;
 use ncollide2d::pipeline::CollisionObjectSlabHandle;
 
+#[derive(Clone)]
 pub struct Droplet {
     pub pos: Vector2<f32>,
     pub size: f32,
@@ -10,7 +11,8 @@ pub struct Droplet {
     pub deleted: bool,
     pub slowing: bool,
     pub collision_handle: CollisionObjectSlabHandle,
-    pub last_trail_y: Option<f32>,
+    pub wobble_amplitude: f32,
+    pub wobble_time: f32,
 }
 
 impl Droplet {
@@ -24,7 +26,8 @@ impl Droplet {
             deleted: false,
             slowing: false,
             collision_handle: CollisionObjectSlabHandle(0),
-            last_trail_y: None,
+            wobble_amplitude: 0.0,
+            wobble_time: 0.0,
         }
     }
 }
@@ -1,10 +1,12 @@
 ﻿use crate::droplet::Droplet;
+use rayon::prelude::*;
 use std::collections::VecDeque;
 use std::ops::{Index, IndexMut};
 
 pub struct Droplets {
     droplets: Vec<Droplet>,
     unused: VecDeque<usize>,
+    rejected_checkouts: usize,
 }
 
 impl Droplets {
@@ -12,6 +14,7 @@ impl Droplets {
         Droplets {
             droplets: Vec::new(),
             unused: VecDeque::new(),
+            rejected_checkouts: 0,
         }
     }
 
@@ -19,6 +22,7 @@ impl Droplets {
         let mut result = Droplets {
             droplets: Vec::with_capacity(capacity),
             unused: VecDeque::with_capacity(capacity),
+            rejected_checkouts: 0,
         };
 
         for i in 0..capacity {
@@ -32,6 +36,15 @@ impl Droplets {
         result
     }
 
+    /// Rebuilds a pool from its droplets and the order in which unused slots get checked out.
+    pub fn from_parts(droplets: Vec<Droplet>, unused: Vec<usize>) -> Self {
+        Droplets {
+            droplets,
+            unused: VecDeque::from(unused),
+            rejected_checkouts: 0,
+        }
+    }
+
     pub fn checkout(&mut self) -> Option<(usize, &mut Droplet)> {
         if let Some(unused) = self.unused.pop_front() {
             let droplet = &mut self.droplets[unused];
@@ -41,6 +54,8 @@ impl Droplets {
             return Some((unused, droplet));
         }
 
+        self.rejected_checkouts += 1;
+
         None
     }
 
@@ -48,6 +63,8 @@ impl Droplets {
         let droplet = &mut self.droplets[index];
 
         droplet.deleted = true;
+        droplet.wobble_amplitude = 0.0;
+        droplet.wobble_time = 0.0;
 
         self.unused.push_back(index);
     }
@@ -59,6 +76,15 @@ impl Droplets {
     pub fn used_count(&self) -> usize {
         self.len() - self.unused.len()
     }
+
+    /// How many checkouts failed because the pool was exhausted.
+    pub fn rejected_checkouts(&self) -> usize {
+        self.rejected_checkouts
+    }
+
+    pub fn unused(&self) -> impl Iterator<Item = usize> + '_ {
+        self.unused.iter().copied()
+    }
 }
 
 impl<'a> IntoIterator for &'a Droplets {
@@ -79,6 +105,15 @@ impl<'a> IntoIterator for &'a mut Droplets {
     }
 }
 
+impl<'a> IntoParallelIterator for &'a mut Droplets {
+    type Item = &'a mut Droplet;
+    type Iter = rayon::slice::IterMut<'a, Droplet>;
+
+    fn into_par_iter(self) -> Self::Iter {
+        self.droplets.par_iter_mut()
+    }
+}
+
 impl Index<usize> for Droplets {
     type Output = Droplet;
 
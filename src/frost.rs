@@ -0,0 +1,219 @@
+﻿use crate::render_gl::Texture;
+use nalgebra::Vector2;
+use rand::prelude::*;
+use std::time::Duration;
+
+// Size of a grid cell, in pixels.
+const CELL_SIZE: f32 = 4.0;
+
+// Ice crystals start from the edges of the glass and from a few points on it.
+const SEEDS_PER_MEGAPIXEL: f32 = 4.0;
+
+// The frost covers at most this fraction of the glass, after roughly this many seconds.
+const MAX_COVERAGE: f32 = 0.6;
+const GROWTH_DURATION: f32 = 300.0;
+
+// Seconds a frozen cell takes to become fully opaque ice.
+const THICKEN_DURATION: f32 = 20.0;
+
+// Random walk steps spent per update at most, and per walker.
+const STEP_BUDGET: usize = 50_000;
+const MAX_WALK_STEPS: usize = 400;
+
+/// Ice crystals growing over the glass, through diffusion-limited aggregation on a coarse grid.
+///
+/// Walkers wander from random cells until they touch the ice and freeze there, which grows the
+/// branching shapes of real frost. The ice is not part of the saved state, so it does not use the
+/// simulation's generator.
+pub struct Frost {
+    columns: usize,
+    rows: usize,
+
+    // Thickness of the ice in every cell, from the bottom row up. Zero is clear glass.
+    ice: Vec<f32>,
+    frozen_count: usize,
+    growth_budget: f32,
+
+    texture: Texture,
+    dirty: bool,
+}
+
+impl Frost {
+    pub fn new(gl: &gl::Gl, width: u32, height: u32) -> Result<Frost, failure::Error> {
+        let mut rng = rand::thread_rng();
+
+        let columns = (width as f32 / CELL_SIZE).ceil() as usize;
+        let rows = (height as f32 / CELL_SIZE).ceil() as usize;
+
+        let texture = Texture::new_with_format(gl, columns as u32, rows as u32, gl::R8)?;
+
+        let mut frost = Frost {
+            columns,
+            rows,
+            ice: vec![0.0; columns * rows],
+            frozen_count: 0,
+            growth_budget: 0.0,
+            texture,
+            dirty: true,
+        };
+
+        for column in 0..columns {
+            frost.freeze(column, 0);
+            frost.freeze(column, rows - 1);
+        }
+
+        for row in 0..rows {
+            frost.freeze(0, row);
+            frost.freeze(columns - 1, row);
+        }
+
+        let seed_count = (width * height) as f32 / 1_000_000.0 * SEEDS_PER_MEGAPIXEL;
+
+        for _ in 0..seed_count.ceil() as usize {
+            let column = rng.gen_range(0, columns);
+            let row = rng.gen_range(0, rows);
+
+            frost.freeze(column, row);
+        }
+
+        frost.upload();
+
+        Ok(frost)
+    }
+
+    /// Grows and thickens the ice, and uploads it if anything changed.
+    pub fn update(&mut self, dt: &Duration) {
+        let dt = dt.as_secs_f32();
+
+        let cell_count = self.ice.len();
+        let max_frozen_count = (cell_count as f32 * MAX_COVERAGE) as usize;
+
+        if self.frozen_count < max_frozen_count {
+            self.growth_budget += cell_count as f32 * MAX_COVERAGE / GROWTH_DURATION * dt;
+
+            self.grow();
+        } else {
+            self.growth_budget = 0.0;
+        }
+
+        for thickness in self.ice.iter_mut() {
+            if *thickness > 0.0 && *thickness < 1.0 {
+                *thickness = (*thickness + dt / THICKEN_DURATION).min(1.0);
+
+                self.dirty = true;
+            }
+        }
+
+        if self.dirty {
+            self.upload();
+        }
+    }
+
+    /// Melts the ice under a droplet of the given radius. The glass may freeze over again later.
+    pub fn melt(&mut self, center: &Vector2<f32>, radius: f32) {
+        // Even the smallest droplet melts the cell it sits in.
+        let radius = radius + CELL_SIZE * 0.5;
+
+        let min_column = ((center.x - radius) / CELL_SIZE).floor().max(0.0) as usize;
+        let min_row = ((center.y - radius) / CELL_SIZE).floor().max(0.0) as usize;
+        let max_column =
+            (((center.x + radius) / CELL_SIZE).floor().max(0.0) as usize).min(self.columns - 1);
+        let max_row =
+            (((center.y + radius) / CELL_SIZE).floor().max(0.0) as usize).min(self.rows - 1);
+
+        for row in min_row..=max_row {
+            for column in min_column..=max_column {
+                let cell = Vector2::new(
+                    (column as f32 + 0.5) * CELL_SIZE,
+                    (row as f32 + 0.5) * CELL_SIZE,
+                );
+
+                let i = row * self.columns + column;
+
+                if self.ice[i] > 0.0 && (cell - center).norm_squared() < radius * radius {
+                    self.ice[i] = 0.0;
+                    self.frozen_count -= 1;
+
+                    self.dirty = true;
+                }
+            }
+        }
+    }
+
+    /// How thick the ice is, in the red channel, stretched over the whole glass.
+    pub fn texture(&self) -> &Texture {
+        &self.texture
+    }
+
+    fn grow(&mut self) {
+        let mut rng = rand::thread_rng();
+
+        let mut steps = 0;
+
+        while self.growth_budget >= 1.0 && steps < STEP_BUDGET {
+            let mut column = rng.gen_range(0, self.columns);
+            let mut row = rng.gen_range(0, self.rows);
+
+            if self.ice[row * self.columns + column] > 0.0 {
+                steps += 1;
+                continue;
+            }
+
+            for _ in 0..MAX_WALK_STEPS {
+                steps += 1;
+
+                if self.touches_ice(column, row) {
+                    self.freeze(column, row);
+                    self.growth_budget -= 1.0;
+
+                    break;
+                }
+
+                match rng.gen_range(0, 4) {
+                    0 => column = column.saturating_sub(1),
+                    1 => column = (column + 1).min(self.columns - 1),
+                    2 => row = row.saturating_sub(1),
+                    _ => row = (row + 1).min(self.rows - 1),
+                }
+            }
+        }
+    }
+
+    fn touches_ice(&self, column: usize, row: usize) -> bool {
+        let frozen = |column: usize, row: usize| self.ice[row * self.columns + column] > 0.0;
+
+        (column > 0 && frozen(column - 1, row))
+            || (column + 1 < self.columns && frozen(column + 1, row))
+            || (row > 0 && frozen(column, row - 1))
+            || (row + 1 < self.rows && frozen(column, row + 1))
+    }
+
+    fn freeze(&mut self, column: usize, row: usize) {
+        let i = row * self.columns + column;
+
+        if self.ice[i] > 0.0 {
+            return;
+        }
+
+        // Fresh ice starts out barely visible and thickens over time.
+        self.ice[i] = f32::EPSILON;
+        self.frozen_count += 1;
+
+        self.dirty = true;
+    }
+
+    fn upload(&mut self) {
+        let rows = self.rows;
+
+        // Images are stored from the top row down.
+        let image = image::GrayImage::from_fn(self.columns as u32, rows as u32, |x, y| {
+            let thickness = self.ice[(rows - 1 - y as usize) * self.columns + x as usize];
+
+            image::Luma([(thickness * 255.0) as u8])
+        });
+
+        self.texture.update_from_luma_image(&image);
+
+        self.dirty = false;
+    }
+}
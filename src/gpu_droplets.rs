@@ -0,0 +1,402 @@
+﻿use crate::droplet::Droplet;
+use crate::droplets::Droplets;
+use crate::quad::Quad;
+use crate::render_gl::buffer::{BufferTypeArray, ShaderStorageBuffer};
+use crate::render_gl::{Error, Program, Shader};
+use nalgebra::Vector2;
+use std::time::Duration;
+
+const COMMON_COMP: &str = include_str!("../assets/shaders/droplets_common.comp");
+const INTEGRATE_COMP: &str = include_str!("../assets/shaders/droplets_integrate.comp");
+const SPAWN_COMP: &str = include_str!("../assets/shaders/droplets_spawn.comp");
+const BIN_COMP: &str = include_str!("../assets/shaders/droplets_bin.comp");
+const MERGE_FIND_COMP: &str = include_str!("../assets/shaders/droplets_merge_find.comp");
+const MERGE_APPLY_COMP: &str = include_str!("../assets/shaders/droplets_merge_apply.comp");
+const RESOLVE_COMP: &str = include_str!("../assets/shaders/droplets_resolve.comp");
+
+// Must match local_size_x in droplets_common.comp.
+const LOCAL_SIZE: usize = 64;
+
+// Droplets only find the droplets they merge with in neighbouring cells, so this is also the
+// largest diameter at which merging is guaranteed.
+const CELL_SIZE: f32 = 64.0;
+
+// Must match the flags in droplets_common.comp. TRAILED, 8, only ever lives on the GPU.
+const DELETED: u32 = 1;
+const SKIPPING: u32 = 2;
+const SLOWING: u32 = 4;
+
+// Binding points of the storage blocks in droplets_common.comp and droplets_spawn.comp.
+const DROPLETS_BINDING: u32 = 0;
+const FREE_LIST_BINDING: u32 = 1;
+const GRID_BINDING: u32 = 2;
+const LINKS_BINDING: u32 = 3;
+const SPAWNS_BINDING: u32 = 4;
+
+// The free list starts with its count and the number of rejected allocations.
+const FREE_LIST_HEADER: usize = 2;
+
+/// A droplet as laid out in the `Droplets` storage block.
+///
+/// The position and size come first and the wobble is contiguous, so the block can be drawn from
+/// directly, as the same instance attributes as `DropletInstance`.
+#[derive(Copy, Clone, Default)]
+#[repr(C)]
+struct GpuDroplet {
+    pos: [f32; 2],
+    size: f32,
+    seed: i32,
+    speed: [f32; 2],
+    wobble_time: f32,
+    wobble_amplitude: f32,
+    last_trail_y: f32,
+    flags: u32,
+}
+
+impl GpuDroplet {
+    fn from_droplet(droplet: &Droplet) -> Self {
+        let mut flags = 0;
+
+        if droplet.deleted {
+            flags |= DELETED;
+        }
+        if droplet.skipping {
+            flags |= SKIPPING;
+        }
+        if droplet.slowing {
+            flags |= SLOWING;
+        }
+
+        GpuDroplet {
+            pos: [droplet.pos.x, droplet.pos.y],
+            // Deleted droplets are drawn as well, so they must not cover anything.
+            size: if droplet.deleted { 0.0 } else { droplet.size },
+            seed: droplet.seed,
+            speed: [droplet.speed.x, droplet.speed.y],
+            wobble_time: droplet.wobble_time,
+            wobble_amplitude: droplet.wobble_amplitude,
+            // Only the GPU sheds trail droplets, so a droplet from anywhere else has not shed
+            // one yet.
+            last_trail_y: 0.0,
+            flags,
+        }
+    }
+
+    fn to_droplet(self) -> Droplet {
+        let mut droplet = Droplet::new();
+
+        droplet.pos = Vector2::new(self.pos[0], self.pos[1]);
+        droplet.size = self.size.max(1.0);
+        droplet.seed = self.seed;
+        droplet.speed = Vector2::new(self.speed[0], self.speed[1]);
+        droplet.wobble_time = self.wobble_time;
+        droplet.wobble_amplitude = self.wobble_amplitude;
+        droplet.deleted = self.flags & DELETED != 0;
+        droplet.skipping = self.flags & SKIPPING != 0;
+        droplet.slowing = self.flags & SLOWING != 0;
+
+        droplet
+    }
+}
+
+/// Simulates the droplets in compute shaders, keeping them in GPU buffers the whole time.
+///
+/// This follows the CPU simulation's rules, but merges overlapping droplets through a uniform
+/// grid rather than a collision world and draws its randomness from a hash of the droplet index
+/// and a per-update seed. Droplet observers are not notified.
+pub struct GpuDroplets {
+    gl: gl::Gl,
+
+    capacity: usize,
+    grid_size: (u32, u32),
+
+    droplets: ShaderStorageBuffer,
+    free_list: ShaderStorageBuffer,
+    grid: ShaderStorageBuffer,
+    links: ShaderStorageBuffer,
+    spawns: ShaderStorageBuffer,
+
+    integrate_program: Program,
+    spawn_program: Program,
+    bin_program: Program,
+    merge_find_program: Program,
+    merge_apply_program: Program,
+    resolve_program: Program,
+}
+
+impl GpuDroplets {
+    /// Whether the context supports compute shaders and storage buffers.
+    pub fn is_supported(gl: &gl::Gl) -> bool {
+        let mut major: gl::types::GLint = 0;
+        let mut minor: gl::types::GLint = 0;
+
+        unsafe {
+            gl.GetIntegerv(gl::MAJOR_VERSION, &mut major);
+            gl.GetIntegerv(gl::MINOR_VERSION, &mut minor);
+        }
+
+        (major, minor) >= (4, 3)
+    }
+
+    pub fn new(
+        gl: &gl::Gl,
+        capacity: usize,
+        width: u32,
+        height: u32,
+    ) -> Result<GpuDroplets, failure::Error> {
+        let grid_size = (
+            (width as f32 / CELL_SIZE).ceil() as u32 + 1,
+            (height as f32 / CELL_SIZE).ceil() as u32 + 1,
+        );
+
+        let droplets = ShaderStorageBuffer::new(gl);
+        droplets.bind();
+        droplets.dynamic_draw_data_null::<GpuDroplet>(capacity);
+        droplets.unbind();
+
+        let free_list = ShaderStorageBuffer::new(gl);
+        free_list.bind();
+        free_list.dynamic_draw_data_null::<i32>(FREE_LIST_HEADER + capacity);
+        free_list.unbind();
+
+        let grid = ShaderStorageBuffer::new(gl);
+        grid.bind();
+        grid.dynamic_draw_data_null::<i32>((grid_size.0 * grid_size.1) as usize);
+        grid.unbind();
+
+        // Each link is a next index, a target index and an absorbed volume.
+        let links = ShaderStorageBuffer::new(gl);
+        links.bind();
+        links.dynamic_draw_data_null::<[i32; 3]>(capacity);
+        links.unbind();
+
+        let spawns = ShaderStorageBuffer::new(gl);
+
+        let gpu_droplets = GpuDroplets {
+            gl: gl.clone(),
+
+            capacity,
+            grid_size,
+
+            droplets,
+            free_list,
+            grid,
+            links,
+            spawns,
+
+            integrate_program: load_compute_shader(gl, INTEGRATE_COMP, "droplets_integrate")?,
+            spawn_program: load_compute_shader(gl, SPAWN_COMP, "droplets_spawn")?,
+            bin_program: load_compute_shader(gl, BIN_COMP, "droplets_bin")?,
+            merge_find_program: load_compute_shader(gl, MERGE_FIND_COMP, "droplets_merge_find")?,
+            merge_apply_program: load_compute_shader(gl, MERGE_APPLY_COMP, "droplets_merge_apply")?,
+            resolve_program: load_compute_shader(gl, RESOLVE_COMP, "droplets_resolve")?,
+        };
+
+        gpu_droplets.upload(&Droplets::with_capacity(capacity));
+
+        Ok(gpu_droplets)
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Advances the simulation, adding the given droplets as (position, size) pairs.
+    pub fn update(&self, dt: &Duration, seed: u32, spawns: &[(Vector2<f32>, f32)]) {
+        let dt = dt.as_secs_f32();
+
+        if dt <= 0.0 {
+            return;
+        }
+
+        self.droplets.bind_base(DROPLETS_BINDING);
+        self.free_list.bind_base(FREE_LIST_BINDING);
+        self.grid.bind_base(GRID_BINDING);
+        self.links.bind_base(LINKS_BINDING);
+
+        let droplet_groups = group_count(self.capacity);
+
+        self.dispatch(&self.integrate_program, droplet_groups, dt, seed);
+
+        if !spawns.is_empty() {
+            let data: Vec<[f32; 4]> = spawns
+                .iter()
+                .map(|(pos, size)| [pos.x, pos.y, *size, 0.0])
+                .collect();
+
+            self.spawns.bind();
+            self.spawns.dynamic_draw_data(&data);
+            self.spawns.unbind();
+
+            self.spawns.bind_base(SPAWNS_BINDING);
+
+            self.spawn_program.set_used();
+
+            if let Some(loc) = self.spawn_program.get_uniform_location("SpawnCount") {
+                self.spawn_program.set_uniform_1i(loc, spawns.len() as i32);
+            }
+
+            self.dispatch(&self.spawn_program, group_count(spawns.len()), dt, seed);
+        }
+
+        self.grid.bind();
+        self.grid.clear_i32(-1);
+        self.grid.unbind();
+
+        self.dispatch(&self.bin_program, droplet_groups, dt, seed);
+        self.dispatch(&self.merge_find_program, droplet_groups, dt, seed);
+        self.dispatch(&self.merge_apply_program, droplet_groups, dt, seed);
+        self.dispatch(&self.resolve_program, droplet_groups, dt, seed);
+
+        unsafe {
+            self.gl
+                .MemoryBarrier(gl::VERTEX_ATTRIB_ARRAY_BARRIER_BIT | gl::BUFFER_UPDATE_BARRIER_BIT);
+        }
+    }
+
+    /// Draws every droplet as an instance of the quad, straight from the storage buffer.
+    /// Free slots have a size of zero and cover nothing.
+    pub fn render(&self, quad: &Quad) {
+        let stride = std::mem::size_of::<GpuDroplet>() as gl::types::GLint;
+
+        quad.vao.bind();
+
+        self.droplets.bind_as::<BufferTypeArray>();
+
+        unsafe {
+            self.gl.EnableVertexAttribArray(3);
+            self.gl
+                .VertexAttribPointer(3, 3, gl::FLOAT, gl::FALSE, stride, std::ptr::null());
+
+            self.gl.EnableVertexAttribArray(4);
+            self.gl.VertexAttribPointer(
+                4,
+                2,
+                gl::FLOAT,
+                gl::FALSE,
+                stride,
+                (6 * std::mem::size_of::<f32>()) as *const gl::types::GLvoid,
+            );
+
+            self.gl.VertexAttribDivisor(3, 1);
+            self.gl.VertexAttribDivisor(4, 1);
+        }
+
+        self.droplets.unbind_as::<BufferTypeArray>();
+
+        unsafe {
+            self.gl.DrawElementsInstanced(
+                gl::TRIANGLES,
+                6,
+                gl::UNSIGNED_BYTE,
+                std::ptr::null(),
+                self.capacity as i32,
+            );
+        }
+
+        quad.vao.unbind();
+    }
+
+    /// Replaces the simulated droplets and the order of the free slots with the given pool's.
+    pub fn upload(&self, droplets: &Droplets) {
+        let data: Vec<GpuDroplet> = droplets
+            .into_iter()
+            .take(self.capacity)
+            .map(GpuDroplet::from_droplet)
+            .collect();
+
+        self.droplets.bind();
+        self.droplets.sub_data(0, &data);
+        self.droplets.unbind();
+
+        // Slots are popped off the end of the free list, while the pool checks them out from
+        // the front.
+        let free_slots: Vec<i32> = droplets
+            .unused()
+            .filter(|&i| i < self.capacity)
+            .map(|i| i as i32)
+            .collect();
+
+        let mut free_list = vec![free_slots.len() as i32, 0];
+        free_list.extend(free_slots.into_iter().rev());
+
+        self.free_list.bind();
+        self.free_list.sub_data(0, &free_list);
+        self.free_list.unbind();
+    }
+
+    /// Reads the simulated droplets back, in the form `Droplets::from_parts` takes.
+    pub fn download(&self) -> (Vec<Droplet>, Vec<usize>) {
+        self.droplets.bind();
+        let data: Vec<GpuDroplet> = self.droplets.get_sub_data(0, self.capacity);
+        self.droplets.unbind();
+
+        self.free_list.bind();
+        let free_count = self.free_list.get_sub_data::<i32>(0, 1)[0].max(0) as usize;
+        let free_slots: Vec<i32> = self
+            .free_list
+            .get_sub_data(FREE_LIST_HEADER, free_count.min(self.capacity));
+        self.free_list.unbind();
+
+        let droplets = data.into_iter().map(GpuDroplet::to_droplet).collect();
+        let unused = free_slots.into_iter().rev().map(|i| i as usize).collect();
+
+        (droplets, unused)
+    }
+
+    fn dispatch(&self, program: &Program, groups: usize, dt: f32, seed: u32) {
+        program.set_used();
+
+        if let Some(loc) = program.get_uniform_location("DropletCount") {
+            program.set_uniform_1i(loc, self.capacity as i32);
+        }
+        if let Some(loc) = program.get_uniform_location("FrameSeed") {
+            program.set_uniform_1i(loc, seed as i32);
+        }
+        if let Some(loc) = program.get_uniform_location("Dt") {
+            program.set_uniform_1f(loc, dt);
+        }
+        if let Some(loc) = program.get_uniform_location("GridSize") {
+            program.set_uniform_2i(loc, self.grid_size.0 as i32, self.grid_size.1 as i32);
+        }
+        if let Some(loc) = program.get_uniform_location("CellSize") {
+            program.set_uniform_1f(loc, CELL_SIZE);
+        }
+
+        unsafe {
+            self.gl.DispatchCompute(groups as u32, 1, 1);
+            self.gl.MemoryBarrier(gl::SHADER_STORAGE_BARRIER_BIT);
+        }
+    }
+}
+
+fn group_count(invocations: usize) -> usize {
+    (invocations as f32 / LOCAL_SIZE as f32).ceil() as usize
+}
+
+fn load_compute_shader(
+    gl: &gl::Gl,
+    source: &str,
+    debug_name: &str,
+) -> Result<Program, failure::Error> {
+    let source = format!(
+        "{}\n{}",
+        COMMON_COMP.trim_start_matches('\u{feff}'),
+        source.trim_start_matches('\u{feff}')
+    );
+
+    let shader = Shader::from_compute_source_str(gl, &source).map_err(|e| match e {
+        Error::CompileError { message, .. } => Error::CompileError {
+            message,
+            name: debug_name.to_string(),
+        },
+        e => e,
+    })?;
+
+    let program = Program::from_shaders(gl, &[shader]).map_err(|message| Error::LinkError {
+        message,
+        name: debug_name.to_string(),
+    })?;
+
+    Ok(program)
+}
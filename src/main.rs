@@ -9,7 +9,12 @@ extern crate nalgebra;
 extern crate ncollide2d;
 extern crate rand;
 
+mod animation;
 mod background;
+mod background_loader;
+mod binary;
+mod blur;
+mod condensation;
 mod config;
 mod config_window;
 mod debug;
@@ -17,24 +22,41 @@ mod debug;
 mod debug_ui;
 mod droplet;
 mod droplets;
+mod frost;
+mod gpu_droplets;
+mod observer;
+mod precipitation;
+mod procedural;
 mod quad;
 mod rain;
 pub mod render_gl;
+mod replay;
+mod slideshow;
+mod state;
+mod statistics;
+mod streaks;
 mod vertex;
+mod wetting;
 
-use crate::config::Config;
+use crate::config::{Config, StartState};
 use crate::config_window::ConfigWindow;
 use crate::debug::failure_to_string;
 #[cfg(feature = "debug")]
 use crate::debug_ui::DebugUi;
+use crate::replay::{Player, Recorder, Replay, ReplayHeader};
+use crate::statistics::Statistics;
 
-use glutin::dpi::{PhysicalPosition, PhysicalSize, Size};
-use glutin::event::{Event, WindowEvent};
+use glutin::dpi::{LogicalSize, PhysicalPosition, PhysicalSize, Size};
+use glutin::event::{ElementState, Event, MouseButton, VirtualKeyCode, WindowEvent};
 use glutin::event_loop::{ControlFlow, EventLoop};
 use glutin::platform::windows::WindowBuilderExtWindows;
 use glutin::window::{Fullscreen, WindowBuilder};
 use glutin::{ContextBuilder, GlRequest};
+use nalgebra::Vector2;
+use std::cell::RefCell;
 use std::env;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
 use std::str::FromStr;
 use std::time::{Duration, Instant};
 use winapi::shared::windef::HWND;
@@ -45,6 +67,8 @@ const MAX_DROPLET_COUNT: usize = 10_000;
 enum Mode {
     Preview(HWND),
     Normal,
+    Interactive,
+    Replay,
 }
 
 fn main() {
@@ -55,7 +79,79 @@ fn main() {
         .unwrap_or(&"/s".to_string())
         .to_ascii_lowercase();
 
+    // Records the seed, frame times and spawns of the run, to reproduce it later with --replay.
+    let recording = option_value(&args, "--record").map(PathBuf::from);
+
+    // Collects droplet statistics, exported as CSV or JSON on exit and whenever S is pressed.
+    let statistics = option_value(&args, "--stats").map(PathBuf::from);
+
     match &arg[..2] {
+        _ if arg == "--interactive" => {
+            // Keeps running on input, pointer drags wipe the glass and clicks spawn droplets.
+            if let Err(e) = run(
+                Mode::Interactive,
+                MAX_DROPLET_COUNT,
+                (3.0, 8.0),
+                recording,
+                None,
+                statistics,
+            ) {
+                println!("{}", failure_to_string(e));
+            }
+        }
+        _ if arg == "--replay" => {
+            // Plays a recording back, optionally fast-forwarding to a given frame.
+            let seek = option_value(&args, "--seek")
+                .and_then(|frame| usize::from_str(frame).ok())
+                .unwrap_or(0);
+
+            let path = args.get(1).map(String::as_str).unwrap_or_default();
+
+            let result = Replay::load(Path::new(path)).and_then(|replay| {
+                let max_droplet_count = replay.header.max_droplet_count;
+                let droplet_size_range = replay.header.droplet_size_range;
+
+                run(
+                    Mode::Replay,
+                    max_droplet_count,
+                    droplet_size_range,
+                    None,
+                    Some((Player::new(replay), seek)),
+                    statistics,
+                )
+            });
+
+            if let Err(e) = result {
+                println!("{}", failure_to_string(e));
+            }
+        }
+        _ if arg == "--replay-info" => {
+            let path = args.get(1).map(String::as_str).unwrap_or_default();
+
+            match Replay::load(Path::new(path)) {
+                Ok(replay) => {
+                    let header = &replay.header;
+
+                    println!("Seed: {}", header.seed);
+                    println!(
+                        "Resolution: {}x{}",
+                        header.resolution.0, header.resolution.1
+                    );
+                    println!("Max droplets: {}", header.max_droplet_count);
+                    println!(
+                        "Droplet sizes: {} to {}",
+                        header.droplet_size_range.0, header.droplet_size_range.1
+                    );
+                    println!("Simulation: {}", header.simulation.name());
+                    println!("Precipitation: {}", header.precipitation.name());
+                    println!("Wetting mask: {}", wetting_mask_name(header.wetting_mask));
+                    println!("Frames: {}", replay.frames.len());
+                    println!("Duration: {:.2}s", replay.duration().as_secs_f64());
+                    println!("Spawned droplets: {}", replay.spawn_count());
+                }
+                Err(e) => println!("{}", failure_to_string(e)),
+            }
+        }
         "/p" => {
             // Preview, parse hwnd from second argument
             let hwnd = {
@@ -66,7 +162,14 @@ fn main() {
 
             let parent_hwnd = unsafe { std::mem::transmute(hwnd) };
 
-            if let Err(e) = run(Mode::Preview(parent_hwnd), 500, (1.0, 5.0)) {
+            if let Err(e) = run(
+                Mode::Preview(parent_hwnd),
+                500,
+                (1.0, 5.0),
+                None,
+                None,
+                None,
+            ) {
                 let err = failure_to_string(e);
                 println!("{}", err);
             }
@@ -78,7 +181,14 @@ fn main() {
             ConfigWindow::init();
         }
         "/s" | _ => {
-            if let Err(e) = run(Mode::Normal, MAX_DROPLET_COUNT, (3.0, 8.0)) {
+            if let Err(e) = run(
+                Mode::Normal,
+                MAX_DROPLET_COUNT,
+                (3.0, 8.0),
+                recording,
+                None,
+                statistics,
+            ) {
                 println!("{}", failure_to_string(e));
             }
 
@@ -91,6 +201,9 @@ fn run(
     mode: Mode,
     max_droplet_count: usize,
     droplet_size_range: (f32, f32),
+    recording: Option<PathBuf>,
+    mut playback: Option<(Player, usize)>,
+    statistics: Option<PathBuf>,
 ) -> Result<(), failure::Error> {
     let event_loop = EventLoop::new();
 
@@ -119,15 +232,28 @@ fn run(
         Mode::Normal => wb
             .with_visible(false)
             .with_fullscreen(Some(Fullscreen::Borderless(event_loop.primary_monitor()))),
+        Mode::Interactive => wb
+            .with_resizable(false)
+            .with_inner_size(Size::Logical(LogicalSize::new(1280.0, 720.0))),
+        Mode::Replay => {
+            let (width, height) = playback.as_ref().unwrap().0.header().resolution;
+
+            wb.with_resizable(false)
+                .with_inner_size(Size::Physical(PhysicalSize::new(width, height)))
+        }
     };
 
     let window = wb.build(&event_loop).unwrap();
 
+    let config = Config::default();
+
     let raw_context = unsafe {
         use glutin::platform::windows::{RawContextExt, WindowExtWindows};
 
         let hwnd = window.hwnd();
-        let cb = ContextBuilder::new().with_gl(GlRequest::Latest);
+        let cb = ContextBuilder::new()
+            .with_gl(GlRequest::Latest)
+            .with_srgb(config.srgb());
 
         cb.build_raw_context(hwnd).unwrap()
     };
@@ -151,6 +277,12 @@ fn run(
     unsafe {
         gl.Enable(gl::BLEND);
         gl.BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+
+        // Shaders output linear colours, which the screen encodes. Textures other than sRGB
+        // ones are written to as they are.
+        if config.srgb() {
+            gl.Enable(gl::FRAMEBUFFER_SRGB);
+        }
     }
 
     let mut context = Option::from(raw_context);
@@ -160,15 +292,84 @@ fn run(
         max_droplet_count,
         droplet_size_range,
         (window_size.width, window_size.height),
-        &Config::default(),
+        &config,
     )?;
 
+    // The tiny preview and replays always start from scratch and never overwrite the saved state.
+    let persistent = matches!(mode, Mode::Normal | Mode::Interactive);
+
+    // Recordings start from scratch as well, so that they can be reproduced from their seed alone.
+    let resumed = persistent && recording.is_none() && resume(&mut rain, &config);
+
+    // Replays and recordings have to start from the same empty glass their seed describes.
+    if !resumed && recording.is_none() && playback.is_none() {
+        rain.warm_up(&config.warm_up());
+    }
+
+    let mut recorder = recording.map(|path| {
+        let recorder = Recorder::new(ReplayHeader {
+            seed: rain.seed(),
+            resolution: (window_size.width, window_size.height),
+            max_droplet_count,
+            droplet_size_range,
+            simulation: rain.simulation(),
+            precipitation: rain.precipitation(),
+            wetting_mask: rain.wetting_mask_fingerprint(),
+        });
+
+        (path, recorder)
+    });
+
+    if let Some((player, seek)) = playback.as_mut() {
+        let header = player.header();
+
+        if header.resolution != (window_size.width, window_size.height) {
+            println!(
+                "The replay was recorded at {}x{} but the window is {}x{}, so it will not be exact.",
+                header.resolution.0, header.resolution.1, window_size.width, window_size.height
+            );
+        }
+
+        if header.simulation != rain.simulation() {
+            println!(
+                "The replay was recorded with the {} simulation but the {} simulation is in use, so it will not be exact.",
+                header.simulation.name(),
+                rain.simulation().name()
+            );
+        }
+
+        if header.wetting_mask != rain.wetting_mask_fingerprint() {
+            println!(
+                "The replay was recorded with wetting mask {} but {} is in use, so it will not be exact.",
+                wetting_mask_name(header.wetting_mask),
+                wetting_mask_name(rain.wetting_mask_fingerprint())
+            );
+        }
+
+        rain.reseed(header.seed);
+        rain.set_precipitation(header.precipitation);
+
+        while player.position() < *seek && play_frame(&mut rain, player).is_some() {}
+    }
+
+    // Registered after seeking, so that only what is actually shown gets measured.
+    let statistics = statistics.map(|path| {
+        let statistics = Rc::new(RefCell::new(Statistics::new()));
+
+        rain.add_observer(statistics.clone());
+
+        (path, statistics)
+    });
+
     let mut instant = Instant::now();
     let mut delta = Duration::default();
 
     let mut initial_mouse_position: Option<PhysicalPosition<f64>> = None;
     let mut skipped_initial_keyboard_events = false;
 
+    let mut cursor_position: Option<Vector2<f32>> = None;
+    let mut wiping = false;
+
     event_loop.run(move |event, _, control_flow| {
         *control_flow = ControlFlow::Poll;
 
@@ -191,13 +392,107 @@ fn run(
                 //  This flag is used to ignore them, until I can figure out why they're there in the first place.
                 skipped_initial_keyboard_events = true;
 
-                rain.update(&delta);
+                match playback.as_mut() {
+                    Some((player, _)) => {
+                        // Once the replay runs out, the simulation is left frozen on its last frame.
+                        delta = play_frame(&mut rain, player).unwrap_or_default();
+                    }
+                    None => {
+                        if let Some((_, recorder)) = recorder.as_mut() {
+                            recorder.frame(delta);
+                        }
+
+                        rain.update(&delta);
+                    }
+                }
+
+                if let Some((_, statistics)) = statistics.as_ref() {
+                    statistics.borrow_mut().sample(&delta, &rain.droplets);
+                }
 
                 window.request_redraw();
             }
             Event::LoopDestroyed => {
+                export_statistics(&statistics);
+
+                if let Some((path, recorder)) = recorder.as_ref() {
+                    if let Err(e) = recorder.replay().save(path) {
+                        println!("{}", failure_to_string(e));
+                    }
+                }
+
+                if persistent {
+                    let (state_path, mask_path) = config.saved_state();
+
+                    if let Err(e) = rain.save_state(&state_path, &mask_path) {
+                        println!("{}", failure_to_string(e));
+                    }
+                }
+
                 context.take(); // Make sure it drops first
             }
+            Event::WindowEvent { event, .. } if matches!(mode, Mode::Interactive) => match event {
+                WindowEvent::CursorMoved { position, .. } => {
+                    // The simulation's origin is at the bottom left of the window.
+                    let position = Vector2::new(
+                        position.x as f32,
+                        window_size.height as f32 - position.y as f32,
+                    );
+
+                    if let (true, Some(previous)) = (wiping, cursor_position) {
+                        rain.wipe(previous, position);
+                    }
+
+                    cursor_position = Some(position);
+                }
+                WindowEvent::MouseInput {
+                    state,
+                    button: MouseButton::Left,
+                    ..
+                } => {
+                    wiping = state == ElementState::Pressed;
+
+                    if let (true, Some(position)) = (wiping, cursor_position) {
+                        rain.spawn_droplet(position);
+
+                        if let Some((_, recorder)) = recorder.as_mut() {
+                            recorder.spawn(position);
+                        }
+                    }
+                }
+                WindowEvent::KeyboardInput { input, .. }
+                    if input.virtual_keycode == Some(VirtualKeyCode::Escape) =>
+                {
+                    *control_flow = ControlFlow::Exit;
+                }
+                WindowEvent::KeyboardInput { input, .. }
+                    if input.state == ElementState::Pressed
+                        && input.virtual_keycode == Some(VirtualKeyCode::S) =>
+                {
+                    export_statistics(&statistics);
+                }
+                WindowEvent::CloseRequested => {
+                    *control_flow = ControlFlow::Exit;
+                }
+                _ => (),
+            },
+            Event::WindowEvent { event, .. } if matches!(mode, Mode::Replay) => match event {
+                WindowEvent::KeyboardInput { input, .. }
+                    if input.virtual_keycode == Some(VirtualKeyCode::Escape) =>
+                {
+                    *control_flow = ControlFlow::Exit;
+                }
+                WindowEvent::KeyboardInput { input, .. }
+                    if input.state == ElementState::Pressed
+                        && input.virtual_keycode == Some(VirtualKeyCode::S) =>
+                {
+                    export_statistics(&statistics);
+                }
+                WindowEvent::CloseRequested => {
+                    *control_flow = ControlFlow::Exit;
+                }
+                _ => (),
+            },
             Event::WindowEvent { event, .. } => match event {
                 WindowEvent::CursorMoved { position, .. } => match initial_mouse_position {
                     Some(p) => {
@@ -244,6 +539,64 @@ fn run(
     Ok(())
 }
 
+fn option_value<'a>(args: &'a [String], name: &str) -> Option<&'a String> {
+    args.iter()
+        .position(|arg| arg.eq_ignore_ascii_case(name))
+        .and_then(|i| args.get(i + 1))
+}
+
+/// Feeds the next recorded frame to the simulation, returning its frame time.
+fn play_frame(rain: &mut rain::Rain, player: &mut Player) -> Option<Duration> {
+    let frame = player.next_frame()?;
+
+    for spawn in frame.spawns.iter() {
+        rain.spawn_droplet(*spawn);
+    }
+
+    rain.update(&frame.delta);
+
+    Some(frame.delta)
+}
+
+/// A wetting mask fingerprint as shown to the user.
+fn wetting_mask_name(fingerprint: Option<u64>) -> String {
+    match fingerprint {
+        Some(fingerprint) => format!("{:016x}", fingerprint),
+        None => "none".to_string(),
+    }
+}
+
+/// Writes the statistics collected so far, the window still open included.
+fn export_statistics(statistics: &Option<(PathBuf, Rc<RefCell<Statistics>>)>) {
+    if let Some((path, statistics)) = statistics {
+        if let Err(e) = statistics.borrow().export(path) {
+            println!("{}", failure_to_string(e));
+        }
+    }
+}
+
+/// Restores the state the config asks to start from, returning whether one was restored.
+fn resume(rain: &mut rain::Rain, config: &Config) -> bool {
+    let candidates = match config.start_state() {
+        StartState::Empty => vec![],
+        StartState::Resume => vec![config.saved_state(), config.rainy_state()],
+        StartState::Rainy => vec![config.rainy_state()],
+    };
+
+    for (state_path, mask_path) in candidates {
+        if !state_path.exists() {
+            continue;
+        }
+
+        match rain.restore_state(&state_path, &mask_path) {
+            Ok(()) => return true,
+            Err(e) => println!("{}", failure_to_string(e)),
+        }
+    }
+
+    false
+}
+
 fn set_screensaver_running(value: bool) {
     unsafe {
         winapi::um::winuser::SystemParametersInfoA(
@@ -0,0 +1,64 @@
+﻿use crate::droplet::Droplet;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Receives droplet lifecycle events from the simulation.
+///
+/// Every callback is invoked from within `Rain::update`, after the simulation has applied the
+/// event, and defaults to doing nothing so observers only implement what they care about.
+pub trait DropletObserver {
+    /// A new droplet was spawned, either by the rain or by the user.
+    fn on_spawn(&mut self, _droplet: &Droplet) {}
+
+    /// `kept` absorbed `absorbed` and has already grown to its new size.
+    /// `absorbed` is freed once every merge of the update has been applied, and its slot may be
+    /// reused after that.
+    fn on_merge(&mut self, _kept: &Droplet, _absorbed: &Droplet) {}
+
+    /// A streak beaded up into a new `child` droplet.
+    ///
+    /// The water was shed seconds earlier, while the droplet that laid the streak kept running.
+    /// `parent_snapshot` is a copy of that droplet as it was when it last fed the streak. It may
+    /// have merged, shrunk further or left the screen since, so it is not a droplet of the
+    /// simulation anymore.
+    fn on_shed(&mut self, _parent_snapshot: &Droplet, _child: &Droplet) {}
+
+    /// A droplet slid off the bottom of the screen. It is freed right after this call.
+    fn on_exit(&mut self, _droplet: &Droplet) {}
+}
+
+/// The observers registered on a simulation, notified in the order they were added.
+#[derive(Default)]
+pub struct Observers {
+    observers: Vec<Rc<RefCell<dyn DropletObserver>>>,
+}
+
+impl Observers {
+    pub fn add(&mut self, observer: Rc<RefCell<dyn DropletObserver>>) {
+        self.observers.push(observer);
+    }
+
+    pub fn spawn(&self, droplet: &Droplet) {
+        for observer in self.observers.iter() {
+            observer.borrow_mut().on_spawn(droplet);
+        }
+    }
+
+    pub fn merge(&self, kept: &Droplet, absorbed: &Droplet) {
+        for observer in self.observers.iter() {
+            observer.borrow_mut().on_merge(kept, absorbed);
+        }
+    }
+
+    pub fn shed(&self, parent_snapshot: &Droplet, child: &Droplet) {
+        for observer in self.observers.iter() {
+            observer.borrow_mut().on_shed(parent_snapshot, child);
+        }
+    }
+
+    pub fn exit(&self, droplet: &Droplet) {
+        for observer in self.observers.iter() {
+            observer.borrow_mut().on_exit(droplet);
+        }
+    }
+}
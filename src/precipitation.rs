@@ -0,0 +1,105 @@
+﻿use crate::render_gl::buffer::ArrayBuffer;
+use crate::vertex::DropletInstance;
+use nalgebra::Vector2;
+use std::time::Duration;
+
+struct Particle {
+    pos: Vector2<f32>,
+    size: f32,
+    age: f32,
+    lifetime: f32,
+    // Varies the look of otherwise identical particles, between 0 and 1.
+    seed: f32,
+    // Size of the droplet the particle leaves behind when its lifetime is over, if any.
+    melts_into: Option<f32>,
+}
+
+/// Whatever lands on the glass that is not a droplet (yet), like snowflakes and hail impacts.
+///
+/// Particles never move or collide, they only live for a while and may then turn into a droplet.
+/// They are drawn as instanced quads, with the `Wobble` attribute of `DropletInstance` carrying
+/// the progress through their lifetime and their seed instead.
+pub struct Particles {
+    particles: Vec<Particle>,
+
+    instance_vbo: ArrayBuffer,
+    instance_count: usize,
+}
+
+impl Particles {
+    pub fn new(gl: &gl::Gl) -> Particles {
+        Particles {
+            particles: Vec::new(),
+            instance_vbo: ArrayBuffer::new(gl),
+            instance_count: 0,
+        }
+    }
+
+    pub fn add(
+        &mut self,
+        pos: Vector2<f32>,
+        size: f32,
+        lifetime: f32,
+        seed: f32,
+        melts_into: Option<f32>,
+    ) {
+        self.particles.push(Particle {
+            pos,
+            size,
+            age: 0.0,
+            lifetime,
+            seed,
+            melts_into,
+        });
+    }
+
+    pub fn clear(&mut self) {
+        self.particles.clear();
+    }
+
+    /// Ages the particles, removing those whose lifetime is over and returning the droplets they
+    /// melted into, in the order the particles were added.
+    pub fn update(&mut self, dt: &Duration) -> Vec<(Vector2<f32>, f32)> {
+        let dt = dt.as_secs_f32();
+
+        let mut melted = Vec::new();
+
+        for particle in self.particles.iter_mut() {
+            particle.age += dt;
+
+            if particle.age >= particle.lifetime {
+                if let Some(size) = particle.melts_into {
+                    melted.push((particle.pos, size));
+                }
+            }
+        }
+
+        self.particles
+            .retain(|particle| particle.age < particle.lifetime);
+
+        let instances: Vec<DropletInstance> = self
+            .particles
+            .iter()
+            .map(|p| DropletInstance {
+                offset: (p.pos.x, p.pos.y, p.size).into(),
+                wobble: (p.age / p.lifetime, p.seed).into(),
+            })
+            .collect();
+
+        self.instance_vbo.bind();
+        self.instance_vbo.dynamic_draw_data(&instances);
+        self.instance_vbo.unbind();
+
+        self.instance_count = instances.len();
+
+        melted
+    }
+
+    pub fn instance_buffer(&self) -> &ArrayBuffer {
+        &self.instance_vbo
+    }
+
+    pub fn instance_count(&self) -> usize {
+        self.instance_count
+    }
+}
@@ -0,0 +1,270 @@
+﻿use crate::config::{Palette, ProceduralBackground};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+// Lights per million pixels at a density of 1.
+const LIGHTS_PER_MEGAPIXEL: f32 = 300.0;
+
+// Light radii, as fractions of the shorter side of the image.
+const MIN_LIGHT_RADIUS: f32 = 0.01;
+const MAX_LIGHT_RADIUS: f32 = 0.06;
+
+// The night sky the lights are scattered over, from top to bottom.
+const SKY_TOP: (f32, f32, f32) = (0.01, 0.01, 0.03);
+const SKY_BOTTOM: (f32, f32, f32) = (0.06, 0.04, 0.07);
+
+const SODIUM: [(f32, f32, f32); 4] = [
+    (1.0, 0.55, 0.15),
+    (1.0, 0.7, 0.3),
+    (1.0, 0.95, 0.85),
+    (0.9, 0.1, 0.05),
+];
+const NEON: [(f32, f32, f32); 4] = [
+    (1.0, 0.2, 0.6),
+    (0.1, 0.9, 1.0),
+    (0.6, 0.2, 1.0),
+    (1.0, 0.9, 0.95),
+];
+const ICE: [(f32, f32, f32); 4] = [
+    (0.5, 0.7, 1.0),
+    (0.3, 0.5, 1.0),
+    (0.85, 0.95, 1.0),
+    (0.6, 1.0, 0.95),
+];
+
+/// Generates a background of the given size on the CPU. The same background and size always
+/// give the same image.
+pub fn generate(background: &ProceduralBackground, width: u32, height: u32) -> image::DynamicImage {
+    let (width, height) = (width.max(1), height.max(1));
+
+    let image = match *background {
+        ProceduralBackground::City {
+            palette,
+            density,
+            seed,
+        } => city_lights(palette, density, seed, width, height),
+        ProceduralBackground::Gradient { top, bottom } => gradient(top, bottom, width, height),
+        ProceduralBackground::Solid(color) => {
+            image::RgbImage::from_pixel(width, height, image::Rgb(to_rgb8(color)))
+        }
+    };
+
+    image::DynamicImage::ImageRgb8(image)
+}
+
+fn gradient(
+    top: (f32, f32, f32),
+    bottom: (f32, f32, f32),
+    width: u32,
+    height: u32,
+) -> image::RgbImage {
+    image::RgbImage::from_fn(width, height, |_, y| {
+        image::Rgb(to_rgb8(lerp(top, bottom, row_fraction(y, height))))
+    })
+}
+
+/// Out of focus lights of a city at night, as seen through a lens focused on the glass.
+fn city_lights(
+    palette: Palette,
+    density: f32,
+    seed: u64,
+    width: u32,
+    height: u32,
+) -> image::RgbImage {
+    let colors = match palette {
+        Palette::Sodium => &SODIUM,
+        Palette::Neon => &NEON,
+        Palette::Ice => &ICE,
+    };
+
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+
+    // Lights add up, so they are accumulated as floats and clamped at the end.
+    let mut pixels = Vec::with_capacity((width * height) as usize);
+
+    for y in 0..height {
+        let sky = lerp(SKY_TOP, SKY_BOTTOM, row_fraction(y, height));
+
+        for _ in 0..width {
+            pixels.push(sky);
+        }
+    }
+
+    let megapixels = (width * height) as f32 / 1_000_000.0;
+    let count = (LIGHTS_PER_MEGAPIXEL * density * megapixels).round() as usize;
+    let shorter_side = width.min(height) as f32;
+
+    for _ in 0..count {
+        let x = rng.gen_range(0.0, width as f32);
+        // Streets and windows are mostly in the lower part of the view.
+        let y = height as f32 * (1.0 - rng.gen::<f32>().powf(1.5));
+
+        // Small, distant lights are more common than large ones.
+        let size = rng.gen::<f32>().powi(3);
+        let radius =
+            shorter_side * (MIN_LIGHT_RADIUS + (MAX_LIGHT_RADIUS - MIN_LIGHT_RADIUS) * size);
+
+        let color = colors[rng.gen_range(0, colors.len())];
+        // Large lights are dimmer, their light being spread over more of the image.
+        let brightness = rng.gen_range(0.15, 0.6) * (1.0 - 0.5 * size);
+
+        draw_light(
+            &mut pixels,
+            width,
+            height,
+            (x, y),
+            radius,
+            scale(color, brightness),
+        );
+    }
+
+    image::RgbImage::from_fn(width, height, |x, y| {
+        image::Rgb(to_rgb8(pixels[(y * width + x) as usize]))
+    })
+}
+
+/// Adds a soft disc with a slightly brighter rim, like the bokeh of a lens.
+fn draw_light(
+    pixels: &mut [(f32, f32, f32)],
+    width: u32,
+    height: u32,
+    center: (f32, f32),
+    radius: f32,
+    color: (f32, f32, f32),
+) {
+    // The edge fades out over this many pixels.
+    let softness = (radius * 0.15).max(1.0);
+
+    let min_x = (center.0 - radius - softness).floor().max(0.0) as u32;
+    let max_x = ((center.0 + radius + softness).ceil() as u32).min(width);
+    let min_y = (center.1 - radius - softness).floor().max(0.0) as u32;
+    let max_y = ((center.1 + radius + softness).ceil() as u32).min(height);
+
+    for y in min_y..max_y {
+        for x in min_x..max_x {
+            let dx = x as f32 + 0.5 - center.0;
+            let dy = y as f32 + 0.5 - center.1;
+            let distance = (dx * dx + dy * dy).sqrt();
+
+            let coverage = ((radius + softness - distance) / (2.0 * softness)).clamp(0.0, 1.0);
+
+            if coverage <= 0.0 {
+                continue;
+            }
+
+            let rim = 0.8 + 0.4 * (distance / radius).min(1.0).powi(4);
+            let pixel = &mut pixels[(y * width + x) as usize];
+
+            *pixel = add(*pixel, scale(color, coverage * rim));
+        }
+    }
+}
+
+fn row_fraction(y: u32, height: u32) -> f32 {
+    if height > 1 {
+        y as f32 / (height - 1) as f32
+    } else {
+        0.0
+    }
+}
+
+fn lerp(a: (f32, f32, f32), b: (f32, f32, f32), t: f32) -> (f32, f32, f32) {
+    add(scale(a, 1.0 - t), scale(b, t))
+}
+
+fn add(a: (f32, f32, f32), b: (f32, f32, f32)) -> (f32, f32, f32) {
+    (a.0 + b.0, a.1 + b.1, a.2 + b.2)
+}
+
+fn scale(a: (f32, f32, f32), s: f32) -> (f32, f32, f32) {
+    (a.0 * s, a.1 * s, a.2 * s)
+}
+
+fn to_rgb8(color: (f32, f32, f32)) -> [u8; 3] {
+    let channel = |c: f32| (c.clamp(0.0, 1.0) * 255.0).round() as u8;
+
+    [channel(color.0), channel(color.1), channel(color.2)]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::GenericImageView;
+
+    const WIDTH: u32 = 64;
+    const HEIGHT: u32 = 48;
+
+    fn city(seed: u64) -> ProceduralBackground {
+        ProceduralBackground::City {
+            palette: Palette::Sodium,
+            density: 50.0,
+            seed,
+        }
+    }
+
+    fn corners(image: &image::DynamicImage) -> [[u8; 3]; 4] {
+        let image = image.to_rgb8();
+        let (right, bottom) = (image.width() - 1, image.height() - 1);
+
+        [
+            image.get_pixel(0, 0).0,
+            image.get_pixel(right, 0).0,
+            image.get_pixel(0, bottom).0,
+            image.get_pixel(right, bottom).0,
+        ]
+    }
+
+    #[test]
+    fn same_seed_gives_same_image() {
+        let first = generate(&city(42), WIDTH, HEIGHT);
+        let second = generate(&city(42), WIDTH, HEIGHT);
+        let other = generate(&city(43), WIDTH, HEIGHT);
+
+        assert_eq!(first.to_bytes(), second.to_bytes());
+        assert_ne!(first.to_bytes(), other.to_bytes());
+    }
+
+    #[test]
+    fn image_has_requested_size() {
+        let backgrounds = [
+            city(42),
+            ProceduralBackground::Gradient {
+                top: (0.0, 0.0, 0.0),
+                bottom: (1.0, 1.0, 1.0),
+            },
+            ProceduralBackground::Solid((0.5, 0.5, 0.5)),
+        ];
+
+        for background in backgrounds.iter() {
+            assert_eq!(
+                generate(background, WIDTH, HEIGHT).dimensions(),
+                (WIDTH, HEIGHT)
+            );
+            assert_eq!(generate(background, 0, 0).dimensions(), (1, 1));
+        }
+    }
+
+    #[test]
+    fn solid_fills_every_corner() {
+        let image = generate(&ProceduralBackground::Solid((1.0, 0.5, 0.0)), WIDTH, HEIGHT);
+
+        assert_eq!(corners(&image), [[255, 128, 0]; 4]);
+    }
+
+    #[test]
+    fn gradient_runs_from_top_to_bottom() {
+        let image = generate(
+            &ProceduralBackground::Gradient {
+                top: (1.0, 0.0, 0.0),
+                bottom: (0.0, 0.0, 1.0),
+            },
+            WIDTH,
+            HEIGHT,
+        );
+
+        assert_eq!(
+            corners(&image),
+            [[255, 0, 0], [255, 0, 0], [0, 0, 255], [0, 0, 255]]
+        );
+    }
+}
@@ -1,11 +1,27 @@
-﻿use crate::background::Background;
-use crate::config::Config;
+﻿use crate::animation::{self, Animation};
+use crate::background::{self, Background, Layout};
+use crate::background_loader::{self, BackgroundLoader, SizeLimit};
+use crate::blur;
+use crate::condensation::Condensation;
+use crate::config::{Config, Precipitation, Simulation, WarmUp};
+use crate::droplet::Droplet;
 use crate::droplets::Droplets;
+use crate::frost::Frost;
+use crate::gpu_droplets::GpuDroplets;
+use crate::observer::{DropletObserver, Observers};
+use crate::precipitation::Particles;
+use crate::procedural;
 use crate::quad::Quad;
 use crate::render_gl::buffer::ArrayBuffer;
 use crate::render_gl::{
-    ColorBuffer, Error, FrameBuffer, Program, Shader, Texture, TextureLoadOptions, Viewport,
+    with_prelude, ColorBuffer, Error, FrameBuffer, Program, Shader, Texture, TextureLoadOptions,
+    Viewport,
 };
+use crate::slideshow::{self, Slideshow};
+use crate::state::SimulationState;
+use crate::streaks::Streaks;
+use crate::vertex::DropletInstance;
+use crate::wetting::WettingMask;
 use nalgebra as na;
 use nalgebra::{Matrix4, Orthographic3, Point3, Translation3, Vector2, Vector3, Vector4};
 use ncollide2d::na::Isometry2;
@@ -14,6 +30,11 @@ use ncollide2d::query::Proximity;
 use ncollide2d::shape::{Ball, ShapeHandle};
 use ncollide2d::world::CollisionWorld;
 use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
+use rayon::prelude::*;
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::path::Path;
 use std::rc::Rc;
 use std::time::Duration;
 
@@ -25,14 +46,74 @@ const DROPLET_SIZE_GRAVITY_THRESHOLD: f32 = 5.0;
 const PRIVATE_GRAVITY_FORCE_FACTOR_Y: f32 = 0.25;
 const PRIVATE_GRAVITY_FORCE_FACTOR_X: f32 = 0.0;
 
+// Snowflakes are bigger than the droplets they melt into.
+const SNOWFLAKE_SCALE: f32 = 1.6;
+const SNOWFLAKE_WATER_FACTOR: f32 = 0.6;
+// Seconds a snowflake sticks to the glass before it has melted.
+const SNOWFLAKE_MELT_RANGE: (f32, f32) = (15.0, 45.0);
+
+// Sleet droplets are just big enough to run, and hit the glass running, in pixels per frame.
+const SLEET_SIZE_RANGE: (f32, f32) = (5.0, 6.5);
+const SLEET_INITIAL_SPEED: f32 = 3.0;
+// Frames a sleet droplet keeps its speed before it slows down like any other droplet.
+const SLEET_RUN_FRAMES: (i32, i32) = (10, 40);
+
+const HAIL_IMPACT_SCALE: f32 = 2.5;
+const HAIL_IMPACT_DURATION: f32 = 0.4;
+// Droplets splashed around a hail impact, relative to the stone.
+const HAIL_SPLASH_COUNT: (usize, usize) = (2, 6);
+const HAIL_SPLASH_SIZE_RANGE: (f32, f32) = (0.15, 0.3);
+const HAIL_SPLASH_DISTANCE_RANGE: (f32, f32) = (0.6, 1.5);
+
+// How much easier droplets start to run on fully water repellent glass, and how much harder on
+// glass that wets easily.
+const WETTING_MOBILITY: f32 = 20.0;
+// Fraction by which repellency lowers the size droplets need to run, or raises it.
+const WETTING_GRAVITY_THRESHOLD_FACTOR: f32 = 0.4;
+// Fraction of the drops that bounce off fully water repellent glass instead of sticking.
+const WETTING_BOUNCE_PROBABILITY: f64 = 0.7;
+// Fraction by which landing drops bead up smaller on repellent glass, or spread out larger.
+const WETTING_SPREAD_FACTOR: f32 = 0.3;
+
+// Only droplets at least this big leave a streak behind.
+const STREAK_SIZE_THRESHOLD: f32 = 6.0;
+// Fraction of its volume a running droplet leaves behind per pixel travelled.
+const STREAK_DEPOSIT_RATE: f32 = 0.00015;
+
+// How long a droplet keeps oscillating after absorbing another, in seconds.
+// The shaders damp the oscillation so it is practically invisible by then.
+const WOBBLE_DURATION: f32 = 1.0;
+const WOBBLE_MAX_AMPLITUDE: f32 = 0.3;
+
+const CONDENSATION_DROPLETS_PER_MEGAPIXEL: f32 = 2000.0;
+const CONDENSATION_DROPLET_SIZE_RANGE: (f32, f32) = (1.0, 3.0);
+
+// How long the droplets take to fade in after warming up, in seconds.
+const FADE_IN_DURATION: f32 = 1.5;
+// Step used to fast-forward the simulation while warming up.
+const WARM_UP_STEP: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
+// How much the ice whitens the frosted background.
+const FROST_WHITENESS: f32 = 0.35;
+
+// Diameter of the "finger" used to wipe the glass in interactive mode.
+const BRUSH_SIZE: f32 = 60.0;
+
 const DROP_VERT: &str = include_str!("../assets/shaders/drop.vert");
 const DROP_FRAG: &str = include_str!("../assets/shaders/drop.frag");
 const DROP_WIPE_VERT: &str = include_str!("../assets/shaders/drop_wipe.vert");
 const DROP_WIPE_FRAG: &str = include_str!("../assets/shaders/drop_wipe.frag");
+const STREAK_VERT: &str = include_str!("../assets/shaders/streak.vert");
+const STREAK_FRAG: &str = include_str!("../assets/shaders/streak.frag");
+const STREAK_WIPE_FRAG: &str = include_str!("../assets/shaders/streak_wipe.frag");
+const PARTICLE_VERT: &str = include_str!("../assets/shaders/particle.vert");
+const SNOW_FRAG: &str = include_str!("../assets/shaders/snow.frag");
+const HAIL_FRAG: &str = include_str!("../assets/shaders/hail.frag");
 const COLORED_QUAD_VERT: &str = include_str!("../assets/shaders/colored_quad.vert");
 const COLORED_QUAD_FRAG: &str = include_str!("../assets/shaders/colored_quad.frag");
 const QUAD_VERT: &str = include_str!("../assets/shaders/quad.vert");
 const FINAL_FRAG: &str = include_str!("../assets/shaders/final.frag");
+const FROST_FRAG: &str = include_str!("../assets/shaders/frost.frag");
 
 fn load_shader(gl: &gl::Gl, vert_source: &str, frag_source: &str, debug_name: &str) -> Program {
     Program::from_shaders(
@@ -49,6 +130,48 @@ fn load_shader(gl: &gl::Gl, vert_source: &str, frag_source: &str, debug_name: &s
     .unwrap()
 }
 
+fn load_background_texture(
+    gl: &gl::Gl,
+    image: &image::DynamicImage,
+    srgb: bool,
+) -> Result<Texture, failure::Error> {
+    let mut options = TextureLoadOptions::rgb();
+    options.gen_mipmaps = true;
+    options.srgb = srgb;
+
+    Texture::from_image(options, gl, image)
+}
+
+/// Decodes an sRGB colour into linear space.
+fn srgb_to_linear((r, g, b): (f32, f32, f32)) -> (f32, f32, f32) {
+    let channel = |c: f32| {
+        if c <= 0.04045 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    };
+
+    (channel(r), channel(g), channel(b))
+}
+
+fn seeded_rng(seed: u64) -> ChaCha8Rng {
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+
+    // Querying the position of a generator that was never used panics, so seek explicitly.
+    rng.set_word_pos(0);
+
+    rng
+}
+
+/// What the collision world needs to hear about a droplet after it was integrated.
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Step {
+    Still,
+    Moved,
+    Exited,
+}
+
 pub struct Rain {
     gl: gl::Gl,
 
@@ -69,22 +192,65 @@ pub struct Rain {
     time_accumulator: f64,
     pub(crate) droplets_accumulator: usize,
 
+    rng_seed: u64,
+    rng: ChaCha8Rng,
+
+    fog_half_life: f32,
+
+    // Seconds left until the droplets are fully faded in.
+    fade_in: f32,
+
     pub(crate) droplets: Droplets,
 
+    observers: Observers,
+
+    // Replaces the CPU simulation when set, in which case `droplets` only stages saved states.
+    gpu_droplets: Option<GpuDroplets>,
+    gpu_droplets_per_second: f64,
+    gpu_spawn_budget: f64,
+    gpu_spawn_queue: Vec<(Vector2<f32>, f32)>,
+
+    // Only laid by the CPU simulation, the GPU one keeps shedding droplets.
+    streaks: Streaks,
+
+    precipitation: Precipitation,
+    wetting_mask: Option<WettingMask>,
+    snowflakes: Particles,
+    hail_impacts: Particles,
+
+    condensation: Condensation,
+
+    frost: Option<Frost>,
+
+    brush_queue: Vec<DropletInstance>,
+    brush_stamps: Vec<DropletInstance>,
+
     black_color_buffer: ColorBuffer,
 
-    background_texture: Rc<Texture>,
     background_mask: Texture,
-    background_buffer: Texture,
 
     background: Background,
+    background_loader: Option<BackgroundLoader>,
+    slideshow: Option<Slideshow>,
+    animation: Option<Animation>,
+    // Animation frames left until the blurred background is updated again.
+    animation_blur_countdown: usize,
+    animation_blur_interval: usize,
+    crossfade: f32,
+    // Whether backgrounds are loaded as sRGB textures.
+    srgb: bool,
     drop_quad: Quad,
     fullscreen_quad: Quad,
 
     drop_program: Program,
     drop_wipe_program: Program,
+    streak_program: Program,
+    streak_wipe_program: Program,
+    snow_program: Program,
+    hail_program: Program,
     colored_quad_program: Program,
     final_program: Program,
+    frost_program: Program,
 
     frame_buffer: FrameBuffer,
 }
@@ -97,7 +263,40 @@ impl Rain {
         window_size: (u32, u32),
         config: &Config,
     ) -> Result<Self, failure::Error> {
-        let droplets: Droplets = Droplets::with_capacity(max_droplet_count);
+        let gpu_droplets = match config.simulation() {
+            Simulation::Gpu if GpuDroplets::is_supported(gl) => Some(GpuDroplets::new(
+                gl,
+                config.gpu_droplet_count(),
+                window_size.0,
+                window_size.1,
+            )?),
+            Simulation::Gpu => {
+                println!("The GPU simulation needs OpenGL 4.3, using the CPU simulation instead.");
+
+                None
+            }
+            Simulation::Cpu => None,
+        };
+
+        let capacity = gpu_droplets
+            .as_ref()
+            .map_or(max_droplet_count, |gpu_droplets| gpu_droplets.capacity());
+
+        let droplets: Droplets = Droplets::with_capacity(capacity);
+
+        // The simulation draws from its own seeded generator, so that its exact position in the
+        // stream can be saved and restored, and a run can be reproduced from its seed.
+        let rng_seed: u64 = rand::thread_rng().gen();
+        let rng = seeded_rng(rng_seed);
+
+        let condensation = Condensation::new(
+            gl,
+            (CONDENSATION_DROPLETS_PER_MEGAPIXEL * (window_size.0 * window_size.1) as f32
+                / 1_000_000.0) as usize,
+            CONDENSATION_DROPLET_SIZE_RANGE,
+            window_size.0,
+            window_size.1,
+        );
 
         let world = CollisionWorld::new(2.0);
 
@@ -123,42 +322,186 @@ impl Rain {
         )
         .into_inner();
 
-        let background_texture = {
-            let fallback_background = config.backgrounds_directory().join("background.jpg");
-
-            let path = config
-                .cached_background()
-                .unwrap_or_else(|| fallback_background.clone());
+        // A chosen procedural background takes the place of any configured image.
+        let procedural_background = config.procedural_background();
+        let background = config
+            .background()
+            .filter(|_| procedural_background.is_none());
+
+        let srgb = config.srgb();
+
+        // Linear colours are kept as floats, which leaves dark tones enough precision.
+        let buffer_format = if srgb { gl::RGBA16F } else { gl::RGBA16 };
+
+        let size_limit = SizeLimit::new(gl, config.background_fit(), window_size);
+
+        // Checked before slideshows, since a directory of numbered frames is an animation.
+        let animation = match background.as_ref() {
+            Some(source) if animation::is_animation(source) => {
+                match Animation::open(
+                    gl,
+                    source,
+                    config.animation_frame_rate(),
+                    size_limit,
+                    config.background_thumbnail(),
+                    srgb,
+                ) {
+                    Ok(animation) => Some(animation),
+                    Err(e) => {
+                        println!("Failed to open animation: {}", e);
+                        None
+                    }
+                }
+            }
+            _ => None,
+        };
 
-            let mut options = TextureLoadOptions::rgb();
-            options.gen_mipmaps = true;
+        let slideshow = match background.as_ref() {
+            Some(source) if animation.is_none() && slideshow::is_slideshow(source) => {
+                match Slideshow::open(
+                    source,
+                    config.slideshow_order(),
+                    config.slideshow_interval(),
+                    size_limit,
+                ) {
+                    Ok(slideshow) => Some(slideshow),
+                    Err(e) => {
+                        println!("Failed to open slideshow: {}", e);
+                        None
+                    }
+                }
+            }
+            _ => None,
+        };
 
-            let image = image::open(&path)
-                .or_else(|_| image::open(&fallback_background))
-                .unwrap();
+        let mut background_loader = None;
 
-            Texture::from_image(options, &gl, &image).unwrap()
+        let placeholder = || {
+            background_loader::placeholder(&config.background_thumbnail(), config.letterbox_color())
         };
 
-        let texture_rc = Rc::<Texture>::new(background_texture);
+        let texture_rc = match (animation.as_ref(), procedural_background) {
+            // The first frame is decoded on a worker as well, and fades in once it is ready.
+            (Some(_), _) => Rc::new(load_background_texture(gl, &placeholder(), srgb)?),
+            (None, Some(procedural_background)) => Rc::new(load_background_texture(
+                gl,
+                &procedural::generate(&procedural_background, window_size.0, window_size.1),
+                srgb,
+            )?),
+            (None, None) => {
+                let fallback_background = config.backgrounds_directory().join("background.jpg");
+
+                let path = match slideshow.as_ref() {
+                    Some(slideshow) => Some(slideshow.current().to_path_buf()),
+                    None => config.cached_background(),
+                }
+                .unwrap_or_else(|| fallback_background.clone());
+
+                // Decoding a large photo takes a while, so the rain starts on a placeholder and
+                // the background fades in once it is ready.
+                background_loader = Some(BackgroundLoader::start(
+                    vec![path, fallback_background],
+                    size_limit,
+                    config.background_thumbnail(),
+                    config.fallback_background(),
+                    window_size,
+                ));
+
+                Rc::new(load_background_texture(gl, &placeholder(), srgb)?)
+            }
+        };
 
         let drop_quad = Quad::default(&gl);
 
-        let background =
-            Background::new(&gl, texture_rc.clone(), window_size.0, window_size.1, 1.0)?;
+        let blur_filter = blur::new_filter(
+            gl,
+            config.blur(),
+            window_size.0,
+            window_size.1,
+            buffer_format,
+            config.blur_radius(),
+            config.blur_components(),
+        )?;
+
+        let layout = Layout {
+            fit: config.background_fit(),
+            focus: config.background_focus(),
+            letterbox_color: if srgb {
+                srgb_to_linear(config.letterbox_color())
+            } else {
+                config.letterbox_color()
+            },
+        };
 
-        let drop_program = load_shader(&gl, DROP_VERT, DROP_FRAG, "drop");
+        let background = Background::new(
+            gl,
+            texture_rc.clone(),
+            window_size.0,
+            window_size.1,
+            buffer_format,
+            layout,
+            blur_filter,
+        )?;
+
+        let drop_program = load_shader(
+            &gl,
+            DROP_VERT,
+            &with_prelude(DROP_FRAG, background::LAYOUT_SHADER),
+            "drop",
+        );
 
         let drop_wipe_program = load_shader(&gl, DROP_WIPE_VERT, DROP_WIPE_FRAG, "drop_wipe");
 
+        let streak_program = load_shader(
+            gl,
+            STREAK_VERT,
+            &with_prelude(STREAK_FRAG, background::LAYOUT_SHADER),
+            "streak",
+        );
+
+        let streak_wipe_program = load_shader(gl, STREAK_VERT, STREAK_WIPE_FRAG, "streak_wipe");
+
+        let snow_program = load_shader(gl, PARTICLE_VERT, SNOW_FRAG, "snow");
+
+        let hail_program = load_shader(gl, PARTICLE_VERT, HAIL_FRAG, "hail");
+
         let colored_quad_program =
             load_shader(&gl, COLORED_QUAD_VERT, COLORED_QUAD_FRAG, "colored_quad");
 
-        let final_program = load_shader(&gl, QUAD_VERT, FINAL_FRAG, "final");
+        let final_program = load_shader(
+            &gl,
+            QUAD_VERT,
+            &with_prelude(FINAL_FRAG, background::LAYOUT_SHADER),
+            "final",
+        );
+
+        let frost_program = load_shader(gl, QUAD_VERT, FROST_FRAG, "frost");
+
+        let wetting_mask = match config.wetting_mask() {
+            Some((path, wettability)) => {
+                match WettingMask::load(&path, wettability, window_size.0, window_size.1) {
+                    Ok(wetting_mask) => Some(wetting_mask),
+                    Err(e) => {
+                        println!(
+                            "Failed to load wetting mask {}: {}",
+                            path.to_string_lossy(),
+                            e
+                        );
+                        None
+                    }
+                }
+            }
+            None => None,
+        };
 
-        let background_mask = Texture::new(&gl, window_size.0, window_size.1)?;
+        let frost = if config.frost() {
+            Some(Frost::new(gl, window_size.0, window_size.1)?)
+        } else {
+            None
+        };
 
-        let background_buffer = Texture::new(&gl, window_size.0, window_size.1)?;
+        // The mask fades by tiny amounts every frame, so it needs more precision than 8 bits.
+        let background_mask = Texture::new_with_format(gl, window_size.0, window_size.1, gl::R32F)?;
 
         let fullscreen_quad =
             Quad::new_with_size(&gl, 0.0, 0.0, window_size.1 as f32, window_size.0 as f32);
@@ -189,8 +532,38 @@ impl Rain {
             time_accumulator: 0.0,
             droplets_accumulator: DROPLETS_PER_SECOND,
 
+            rng_seed,
+            rng,
+
+            fog_half_life: config.fog_half_life(),
+
+            fade_in: 0.0,
+
             droplets,
 
+            observers: Observers::default(),
+
+            // A bigger pool fills up at the same pace as the CPU simulation's.
+            gpu_droplets_per_second: DROPLETS_PER_SECOND as f64 * capacity as f64
+                / max_droplet_count as f64,
+            gpu_droplets,
+            gpu_spawn_budget: 0.0,
+            gpu_spawn_queue: Vec::new(),
+
+            streaks: Streaks::new(gl),
+
+            precipitation: config.precipitation(),
+            wetting_mask,
+            snowflakes: Particles::new(gl),
+            hail_impacts: Particles::new(gl),
+
+            condensation,
+
+            frost,
+
+            brush_queue: Vec::new(),
+            brush_stamps: Vec::new(),
+
             world,
             collision_group,
             contacts_query,
@@ -200,25 +573,77 @@ impl Rain {
 
             black_color_buffer,
 
-            background_texture: texture_rc,
             background_mask,
-            background_buffer,
 
             background,
+            background_loader,
+            slideshow,
+            animation,
+            animation_blur_countdown: 0,
+            animation_blur_interval: config.animation_blur_interval(),
+            crossfade: config.crossfade(),
+            srgb,
             drop_quad,
             fullscreen_quad,
 
             drop_program,
             drop_wipe_program,
+            streak_program,
+            streak_wipe_program,
+            snow_program,
+            hail_program,
             colored_quad_program,
             final_program,
+            frost_program,
 
             frame_buffer,
         })
     }
 
     pub fn update(&mut self, delta: &Duration) {
-        let mut rng = rand::thread_rng();
+        self.fade_in = (self.fade_in - delta.as_secs_f32()).max(0.0);
+
+        let loaded = self.background_loader.as_ref().and_then(|l| l.poll());
+
+        if loaded.is_some() {
+            self.background_loader = None;
+        }
+
+        let next = loaded.or_else(|| self.slideshow.as_mut().and_then(|s| s.update(delta)));
+
+        if let Some(image) = next {
+            // The current background stays if the new one cannot be shown.
+            match load_background_texture(&self.gl, &image, self.srgb) {
+                Ok(texture) => self
+                    .background
+                    .set_texture(Rc::new(texture), self.crossfade),
+                Err(e) => println!("Failed to show background: {}", e),
+            }
+        }
+
+        if let Some(animation) = self.animation.as_mut() {
+            let playing = animation.is_playing();
+
+            if animation.update(delta) {
+                if !playing {
+                    // The first frame fades in over the placeholder, like a still background.
+                    self.background
+                        .set_texture(animation.texture(), self.crossfade);
+                } else {
+                    let blur = self.animation_blur_countdown == 0;
+
+                    self.animation_blur_countdown = if blur {
+                        self.animation_blur_interval - 1
+                    } else {
+                        self.animation_blur_countdown - 1
+                    };
+
+                    self.background.set_frame(animation.texture(), blur);
+                }
+            }
+        }
+
+        self.background.step(delta);
 
         self.time_accumulator += delta.as_secs_f64();
 
@@ -228,19 +653,48 @@ impl Rain {
             self.droplets_accumulator += DROPLETS_PER_SECOND;
         }
 
+        // Snowflakes melt into droplets, which are simulated like any other from then on.
+        for (pos, size) in self.snowflakes.update(delta) {
+            self.add_droplet(pos, size);
+        }
+
+        self.hail_impacts.update(delta);
+
         // Updates
-        {
-            Self::gravity_non_linear(&mut self.droplets, &mut self.world, &mut rng, delta);
+        if self.gpu_droplets.is_some() {
+            self.update_gpu(delta);
+
+            // The droplets never leave the GPU, so only the brush wipes the condensation.
+            self.condensation.update(delta);
+        } else {
+            Self::wobble(&mut self.droplets, delta);
+
+            Self::gravity_non_linear(
+                &mut self.droplets,
+                &mut self.world,
+                &mut self.rng,
+                &self.observers,
+                self.wetting_mask.as_ref(),
+                delta,
+            );
 
-            Self::trail(
+            Self::streak(
                 &mut self.droplets,
                 &mut self.world,
-                &mut rng,
-                &self.collision_group,
-                &self.contacts_query,
+                &mut self.rng,
+                &mut self.streaks,
                 &delta,
             );
 
+            for bead in self.streaks.update(&mut self.rng, delta) {
+                if let Some(i) = self.place_droplet(bead.pos, bead.size) {
+                    self.observers
+                        .shed(&bead.parent_snapshot, &self.droplets[i]);
+                }
+            }
+
+            self.streaks.upload();
+
             self.updates.clear();
 
             // We get an "allowance" of DROPLETS_PER_SECOND every second.
@@ -249,30 +703,17 @@ impl Rain {
             // TODO: Any better way to spend these more evenly?
             // TODO: What happens when budget > fps?
             if self.droplets_accumulator > 0
-                && rng.gen_bool(self.time_accumulator.max(0.0).min(1.0))
+                && self.rng.gen_bool(self.time_accumulator.max(0.0).min(1.0))
             {
-                if let Some((i, d)) = self.droplets.checkout() {
-                    d.pos = Vector2::new(
-                        rng.gen_range(0.0, self.viewport.w as f32),
-                        rng.gen_range(0.0, self.viewport.h as f32),
-                    );
-                    d.size = rng.gen_range(self.droplet_size_range.0, self.droplet_size_range.1);
-
-                    let shape_handle = ShapeHandle::new(Ball::new(d.size * 0.5));
-
-                    let handle = self
-                        .world
-                        .add(
-                            Isometry2::new(d.pos.clone_owned(), na::zero()),
-                            shape_handle,
-                            self.collision_group,
-                            self.contacts_query,
-                            i,
-                        )
-                        .0;
-
-                    d.collision_handle = handle;
+                let pos = Vector2::new(
+                    self.rng.gen_range(0.0, self.viewport.w as f32),
+                    self.rng.gen_range(0.0, self.viewport.h as f32),
+                );
+                let size = self
+                    .rng
+                    .gen_range(self.droplet_size_range.0, self.droplet_size_range.1);
 
+                if self.fall(pos, size) {
                     self.droplets_accumulator -= 1;
                 }
             }
@@ -322,7 +763,18 @@ impl Rain {
                     .cbrt()
                         * 2.0;
 
+                    // The bigger the absorbed droplet is relative to the result, the harder it wobbles.
+                    keep_droplet.wobble_amplitude = (keep_droplet.wobble_amplitude
+                        + WOBBLE_MAX_AMPLITUDE * delete_droplet_size / keep_droplet.size)
+                        .min(WOBBLE_MAX_AMPLITUDE);
+                    keep_droplet.wobble_time = 0.0;
+
                     keep.set_shape(ShapeHandle::new(Ball::new(keep_droplet.size * 0.5)));
+
+                    self.observers.merge(
+                        &self.droplets[keep_droplet_index],
+                        &self.droplets[delete_droplet_index],
+                    );
                 }
             }
 
@@ -332,51 +784,465 @@ impl Rain {
                     self.world.remove(&[*delete_handle]);
                 }
             }
+
+            // Droplets soak up the condensation they pass over, just like they wipe the mask.
+            // The condensation is not part of the saved state, so it does not use the simulation's
+            // generator.
+            let mut rng = rand::thread_rng();
+
+            for droplet in self.droplets.into_iter().filter(|d| !d.deleted) {
+                self.condensation
+                    .wipe(&mut rng, &droplet.pos, droplet.size * 0.5);
+
+                if let Some(frost) = self.frost.as_mut() {
+                    frost.melt(&droplet.pos, droplet.size * 0.5);
+                }
+            }
+
+            self.condensation.update(delta);
+        }
+
+        if let Some(frost) = self.frost.as_mut() {
+            frost.update(delta);
         }
+
+        // Wipes queued since the last update are drawn into the mask by the next render.
+        self.brush_stamps.clear();
+        std::mem::swap(&mut self.brush_queue, &mut self.brush_stamps);
     }
 
-    pub fn render(&self, delta: &Duration) {
-        let matrix = &self.projection_matrix * &self.view_matrix;
+    fn update_gpu(&mut self, delta: &Duration) {
+        if self.gpu_droplets.is_none() {
+            return;
+        }
 
-        let resolution = Vector2::new(self.viewport.w as f32, self.viewport.h as f32);
+        // The pool is much larger, so the rain is spread evenly over the frames instead.
+        self.gpu_spawn_budget += self.gpu_droplets_per_second * delta.as_secs_f64();
 
-        // Background pass
-        {
-            self.background.prepass(
-                &self.gl,
-                &self.view_matrix,
-                &self.projection_matrix,
-                &resolution,
+        let count = self.gpu_spawn_budget.floor();
+        self.gpu_spawn_budget -= count;
+
+        for _ in 0..count as usize {
+            let pos = Vector2::new(
+                self.rng.gen_range(0.0, self.viewport.w as f32),
+                self.rng.gen_range(0.0, self.viewport.h as f32),
             );
+            let size = self
+                .rng
+                .gen_range(self.droplet_size_range.0, self.droplet_size_range.1);
 
-            self.frame_buffer.bind();
-            self.frame_buffer.attach_texture(&self.background_buffer);
+            self.fall(pos, size);
+        }
 
-            self.background.render(
-                &self.gl,
-                &self.view_matrix,
-                &self.projection_matrix,
-                &resolution,
-            );
+        if let Some(gpu_droplets) = self.gpu_droplets.as_ref() {
+            gpu_droplets.update(delta, self.rng.gen(), &self.gpu_spawn_queue);
+        }
 
-            self.frame_buffer.unbind();
+        self.gpu_spawn_queue.clear();
+    }
+
+    /// Makes an empty glass look like it has been raining for a while, then fades the droplets in.
+    pub fn warm_up(&mut self, warm_up: &WarmUp) {
+        match *warm_up {
+            WarmUp::None => return,
+            WarmUp::Scatter(count) => {
+                for _ in 0..count {
+                    let pos = Vector2::new(
+                        self.rng.gen_range(0.0, self.viewport.w as f32),
+                        self.rng.gen_range(0.0, self.viewport.h as f32),
+                    );
+                    let size = self
+                        .rng
+                        .gen_range(self.droplet_size_range.0, self.droplet_size_range.1);
+
+                    if !self.add_droplet(pos, size) {
+                        break;
+                    }
+                }
+            }
+            WarmUp::FastForward(seconds) => {
+                let steps = (seconds / WARM_UP_STEP.as_secs_f32()).round() as usize;
+
+                for _ in 0..steps {
+                    self.update(&WARM_UP_STEP);
+                }
+            }
         }
 
-        // Mask pass
+        self.fade_in = FADE_IN_DURATION;
+    }
+
+    /// Spawns a droplet of random size at the given position, e.g. where the user clicked.
+    pub fn spawn_droplet(&mut self, pos: Vector2<f32>) {
+        let size = self
+            .rng
+            .gen_range(self.droplet_size_range.0, self.droplet_size_range.1);
+
+        self.add_droplet(pos, size);
+    }
+
+    /// Wipes the fog and condensation along a stroke, as if a finger was dragged across the glass.
+    pub fn wipe(&mut self, from: Vector2<f32>, to: Vector2<f32>) {
+        let mut rng = rand::thread_rng();
+
+        let stroke = to - from;
+        let steps = (stroke.norm() / (BRUSH_SIZE * 0.25)).ceil().max(1.0) as usize;
+
+        for step in 0..=steps {
+            let pos = from + stroke * (step as f32 / steps as f32);
+
+            self.brush_queue.push(DropletInstance {
+                offset: (pos.x, pos.y, BRUSH_SIZE).into(),
+                wobble: (0.0, 0.0).into(),
+            });
+
+            self.condensation.wipe(&mut rng, &pos, BRUSH_SIZE * 0.5);
+
+            if let Some(frost) = self.frost.as_mut() {
+                frost.melt(&pos, BRUSH_SIZE * 0.5);
+            }
+        }
+    }
+
+    /// Registers an observer to be notified of droplet spawns, merges, sheds and exits.
+    pub fn add_observer(&mut self, observer: Rc<RefCell<dyn DropletObserver>>) {
+        self.observers.add(observer);
+    }
+
+    /// Where the droplets are simulated, which is the CPU if the GPU simulation is not supported.
+    pub fn simulation(&self) -> Simulation {
+        if self.gpu_droplets.is_some() {
+            Simulation::Gpu
+        } else {
+            Simulation::Cpu
+        }
+    }
+
+    pub fn precipitation(&self) -> Precipitation {
+        self.precipitation
+    }
+
+    /// Changes what falls from now on. Whatever already landed stays.
+    pub fn set_precipitation(&mut self, precipitation: Precipitation) {
+        self.precipitation = precipitation;
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.rng_seed
+    }
+
+    /// The fingerprint of the wetting mask in use, if any.
+    pub fn wetting_mask_fingerprint(&self) -> Option<u64> {
+        self.wetting_mask.as_ref().map(WettingMask::fingerprint)
+    }
+
+    /// Restarts the simulation's random stream from the given seed, e.g. to reproduce a recording.
+    pub fn reseed(&mut self, seed: u64) {
+        self.rng_seed = seed;
+        self.rng = seeded_rng(seed);
+    }
+
+    /// Saves the simulation and the fog mask, so that a later run can resume from them.
+    pub fn save_state(&self, path: &Path, mask_path: &Path) -> Result<(), failure::Error> {
+        let (droplets, unused) = match self.gpu_droplets.as_ref() {
+            Some(gpu_droplets) => gpu_droplets.download(),
+            None => (
+                self.droplets.into_iter().cloned().collect(),
+                self.droplets.unused().collect(),
+            ),
+        };
+
+        let state = SimulationState {
+            resolution: (self.viewport.w as u32, self.viewport.h as u32),
+            time_accumulator: self.time_accumulator,
+            droplets_accumulator: self.droplets_accumulator,
+            rng_seed: self.rng_seed,
+            rng_word_pos: self.rng.get_word_pos(),
+            droplets,
+            unused,
+        };
+
+        state.save(path)?;
+
+        self.background_mask.to_luma_image().save(mask_path)?;
+
+        Ok(())
+    }
+
+    /// Resumes from a state saved by `save_state`.
+    ///
+    /// A state saved at a different resolution is stretched to fit and, if the droplet pool has a
+    /// different capacity, only as many live droplets as fit are kept. A missing mask leaves the
+    /// glass fogged.
+    pub fn restore_state(&mut self, path: &Path, mask_path: &Path) -> Result<(), failure::Error> {
+        let state = SimulationState::load(path)?;
+
+        let scale = Vector2::new(
+            self.viewport.w as f32 / state.resolution.0 as f32,
+            self.viewport.h as f32 / state.resolution.1 as f32,
+        );
+
+        let mut droplets = state.droplets;
+
+        for droplet in droplets.iter_mut() {
+            droplet.pos.component_mul_assign(&scale);
+        }
+
+        self.droplets = if droplets.len() == self.droplets.len() {
+            Droplets::from_parts(droplets, state.unused)
+        } else {
+            let mut pool = Droplets::with_capacity(self.droplets.len());
+
+            for droplet in droplets.into_iter().filter(|d| !d.deleted) {
+                match pool.checkout() {
+                    Some((_, d)) => *d = droplet,
+                    None => break,
+                }
+            }
+
+            pool
+        };
+
+        self.world = CollisionWorld::new(2.0);
+
+        for (i, d) in (&mut self.droplets).into_iter().enumerate() {
+            if d.deleted {
+                continue;
+            }
+
+            let shape_handle = ShapeHandle::new(Ball::new(d.size * 0.5));
+
+            d.collision_handle = self
+                .world
+                .add(
+                    Isometry2::new(d.pos.clone_owned(), na::zero()),
+                    shape_handle,
+                    self.collision_group,
+                    self.contacts_query,
+                    i,
+                )
+                .0;
+        }
+
+        self.updates.clear();
+
+        // Streaks are not part of the saved state and would point at the wrong droplets.
+        self.streaks.clear();
+        self.snowflakes.clear();
+        self.hail_impacts.clear();
+
+        if let Some(gpu_droplets) = self.gpu_droplets.as_ref() {
+            gpu_droplets.upload(&self.droplets);
+        }
+
+        self.time_accumulator = state.time_accumulator;
+        self.droplets_accumulator = state.droplets_accumulator;
+
+        self.reseed(state.rng_seed);
+        self.rng.set_word_pos(state.rng_word_pos);
+
+        if mask_path.exists() {
+            let (width, height) = self.background_mask.dimensions();
+
+            let mut mask = image::open(mask_path)?.to_luma8();
+
+            if mask.dimensions() != (width, height) {
+                mask = image::imageops::resize(
+                    &mask,
+                    width,
+                    height,
+                    image::imageops::FilterType::Triangle,
+                );
+            }
+
+            self.background_mask.update_from_luma_image(&mask);
+        }
+
+        Ok(())
+    }
+
+    /// Lets a drop fall onto the glass, where it may bounce off or stick depending on how water
+    /// repellent the glass is there. Returns whether it is done with, either way.
+    fn fall(&mut self, pos: Vector2<f32>, size: f32) -> bool {
+        let repellency = match self.wetting_mask.as_ref() {
+            Some(wetting_mask) => wetting_mask.repellency(&pos),
+            None => return self.precipitate(pos, size),
+        };
+
+        if repellency > 0.0
+            && self
+                .rng
+                .gen_bool(repellency as f64 * WETTING_BOUNCE_PROBABILITY)
         {
-            self.frame_buffer.bind();
-            self.frame_buffer.attach_texture(&self.background_mask);
+            return true;
+        }
 
-            unsafe {
-                self.gl.BlendFuncSeparate(
-                    gl::SRC_ALPHA,
-                    gl::ONE_MINUS_SRC_ALPHA,
-                    gl::ZERO,
-                    gl::ONE,
+        let size = (size * (1.0 - WETTING_SPREAD_FACTOR * repellency)).max(1.0);
+
+        self.precipitate(pos, size)
+    }
+
+    /// Lets something fall onto the glass at the given position, depending on the precipitation.
+    /// Returns whether it fitted.
+    fn precipitate(&mut self, pos: Vector2<f32>, size: f32) -> bool {
+        match self.precipitation {
+            Precipitation::Rain => self.add_droplet(pos, size),
+            Precipitation::Snow => {
+                let melt_duration = self
+                    .rng
+                    .gen_range(SNOWFLAKE_MELT_RANGE.0, SNOWFLAKE_MELT_RANGE.1);
+                let seed = self.rng.gen();
+
+                self.snowflakes.add(
+                    pos,
+                    size * SNOWFLAKE_SCALE,
+                    melt_duration,
+                    seed,
+                    Some((size * SNOWFLAKE_WATER_FACTOR).max(1.0)),
                 );
+
+                true
             }
+            Precipitation::Sleet => {
+                let size = self.rng.gen_range(SLEET_SIZE_RANGE.0, SLEET_SIZE_RANGE.1);
+
+                // The GPU simulation only takes positions and sizes, so sleet starts still there.
+                if self.gpu_droplets.is_some() {
+                    return self.add_droplet(pos, size);
+                }
+
+                let run_frames = self.rng.gen_range(SLEET_RUN_FRAMES.0, SLEET_RUN_FRAMES.1);
+
+                match self.place_droplet(pos, size) {
+                    Some(i) => {
+                        let droplet = &mut self.droplets[i];
+
+                        droplet.speed.y = SLEET_INITIAL_SPEED;
+                        droplet.seed = run_frames;
+
+                        self.observers.spawn(droplet);
+
+                        true
+                    }
+                    None => false,
+                }
+            }
+            Precipitation::Hail => {
+                let seed = self.rng.gen();
+
+                self.hail_impacts.add(
+                    pos,
+                    size * HAIL_IMPACT_SCALE,
+                    HAIL_IMPACT_DURATION,
+                    seed,
+                    None,
+                );
+
+                // The impact knocks the fog off the glass.
+                self.brush_queue.push(DropletInstance {
+                    offset: (pos.x, pos.y, size * HAIL_IMPACT_SCALE * 0.5).into(),
+                    wobble: (0.0, 0.0).into(),
+                });
+
+                let count = self
+                    .rng
+                    .gen_range(HAIL_SPLASH_COUNT.0, HAIL_SPLASH_COUNT.1 + 1);
+
+                for _ in 0..count {
+                    let angle = self.rng.gen_range(0.0, std::f32::consts::PI * 2.0);
+                    let distance = size
+                        * self
+                            .rng
+                            .gen_range(HAIL_SPLASH_DISTANCE_RANGE.0, HAIL_SPLASH_DISTANCE_RANGE.1);
+                    let splash_size = size
+                        * self
+                            .rng
+                            .gen_range(HAIL_SPLASH_SIZE_RANGE.0, HAIL_SPLASH_SIZE_RANGE.1);
+
+                    self.add_droplet(
+                        pos + Vector2::new(angle.cos(), angle.sin()) * distance,
+                        splash_size.max(1.0),
+                    );
+                }
+
+                true
+            }
+        }
+    }
+
+    fn add_droplet(&mut self, pos: Vector2<f32>, size: f32) -> bool {
+        // Landing droplets melt a hole into the ice. The CPU simulation keeps melting under them.
+        if let Some(frost) = self.frost.as_mut() {
+            frost.melt(&pos, size * 0.5);
+        }
+
+        if self.gpu_droplets.is_some() {
+            self.gpu_spawn_queue.push((pos, size));
+
+            return true;
+        }
+
+        if let Some(i) = self.place_droplet(pos, size) {
+            self.observers.spawn(&self.droplets[i]);
+
+            return true;
+        }
+
+        false
+    }
+
+    /// Puts a fresh droplet into a free slot of the CPU simulation, without notifying anyone.
+    fn place_droplet(&mut self, pos: Vector2<f32>, size: f32) -> Option<usize> {
+        let (i, d) = self.droplets.checkout()?;
+
+        // The slot may still hold the speed and timers of the droplet that used it last.
+        *d = Droplet::new();
+
+        d.pos = pos;
+        d.size = size;
+
+        let shape_handle = ShapeHandle::new(Ball::new(d.size * 0.5));
+
+        let handle = self
+            .world
+            .add(
+                Isometry2::new(d.pos.clone_owned(), na::zero()),
+                shape_handle,
+                self.collision_group,
+                self.contacts_query,
+                i,
+            )
+            .0;
+
+        d.collision_handle = handle;
+
+        Some(i)
+    }
+
+    pub fn render(&self, delta: &Duration) {
+        let matrix = &self.projection_matrix * &self.view_matrix;
+
+        let resolution = Vector2::new(self.viewport.w as f32, self.viewport.h as f32);
 
+        let opacity = 1.0 - self.fade_in / FADE_IN_DURATION;
+
+        // Background pass, only when the blurred background is out of date
+        self.background.update(&self.gl);
+
+        // Mask pass
+        {
+            self.frame_buffer.bind();
+            self.frame_buffer.attach_texture(&self.background_mask);
+
+            // Re-fog by multiplying the mask with the fraction that survives this frame, which
+            // makes the decay exponential and independent of the frame rate.
             {
+                let remaining = 0.5f32.powf(delta.as_secs_f32() / self.fog_half_life);
+
+                unsafe {
+                    self.gl.BlendFunc(gl::ZERO, gl::SRC_COLOR);
+                }
+
                 self.colored_quad_program.set_used();
 
                 if let Some(loc) = self.colored_quad_program.get_uniform_location("MVP") {
@@ -387,13 +1253,27 @@ impl Rain {
                 if let Some(loc) = self.colored_quad_program.get_uniform_location("Color") {
                     self.colored_quad_program.set_uniform_4f(
                         loc,
-                        &Vector4::new(0.0, 0.0, 0.0, 0.25 * delta.as_secs_f32()),
+                        &Vector4::new(remaining, remaining, remaining, remaining),
                     );
                 }
 
                 self.fullscreen_quad.render(&self.gl);
             }
 
+            unsafe {
+                self.gl.BlendFuncSeparate(
+                    gl::SRC_ALPHA,
+                    gl::ONE_MINUS_SRC_ALPHA,
+                    gl::ZERO,
+                    gl::ONE,
+                );
+            }
+
+            // Ice fogs the glass over again, before the droplets melt their way through it.
+            if let Some(frost) = self.frost.as_ref() {
+                self.render_frost(frost, &matrix, &Vector4::new(0.0, 0.0, 0.0, 1.0));
+            }
+
             {
                 self.drop_wipe_program.set_used();
 
@@ -406,6 +1286,21 @@ impl Rain {
                 }
 
                 self.render_droplets(&self.gl, &self.drop_quad, &self.droplets);
+
+                if !self.brush_stamps.is_empty() {
+                    Self::render_instance_data(&self.gl, &self.drop_quad, &self.brush_stamps);
+                }
+            }
+
+            {
+                self.streak_wipe_program.set_used();
+
+                if let Some(loc) = self.streak_wipe_program.get_uniform_location("MVP") {
+                    self.streak_wipe_program
+                        .set_uniform_matrix_4fv(loc, &matrix);
+                }
+
+                self.streaks.render(&self.gl);
             }
 
             unsafe {
@@ -426,12 +1321,14 @@ impl Rain {
                 self.final_program.set_uniform_matrix_4fv(loc, &matrix);
             }
 
+            self.background.set_layout_uniforms(&self.final_program);
+
             if let Some(loc) = self.final_program.get_uniform_location("Texture0") {
-                self.background_buffer.bind_at(0);
+                self.background.blurred().bind_at(0);
                 self.final_program.set_uniform_1i(loc, 0);
             }
             if let Some(loc) = self.final_program.get_uniform_location("Texture1") {
-                self.background_texture.bind_at(1);
+                self.background.texture().bind_at(1);
                 self.final_program.set_uniform_1i(loc, 1);
             }
             if let Some(loc) = self.final_program.get_uniform_location("Mask") {
@@ -440,6 +1337,39 @@ impl Rain {
             }
 
             self.fullscreen_quad.render(&self.gl);
+
+            if let Some(frost) = self.frost.as_ref() {
+                self.render_frost(
+                    frost,
+                    &matrix,
+                    &Vector4::new(0.9, 0.95, 1.0, FROST_WHITENESS),
+                );
+            }
+        }
+
+        {
+            self.streak_program.set_used();
+
+            if let Some(loc) = self.streak_program.get_uniform_location("Resolution") {
+                self.streak_program.set_uniform_2f(loc, &resolution);
+            }
+
+            if let Some(loc) = self.streak_program.get_uniform_location("Opacity") {
+                self.streak_program.set_uniform_1f(loc, opacity);
+            }
+
+            if let Some(loc) = self.streak_program.get_uniform_location("MVP") {
+                self.streak_program.set_uniform_matrix_4fv(loc, &matrix);
+            }
+
+            self.background.set_layout_uniforms(&self.streak_program);
+
+            if let Some(loc) = self.streak_program.get_uniform_location("Texture") {
+                self.background.texture().bind_at(0);
+                self.streak_program.set_uniform_1i(loc, 0);
+            }
+
+            self.streaks.render(&self.gl);
         }
 
         {
@@ -449,48 +1379,136 @@ impl Rain {
                 self.drop_program.set_uniform_2f(loc, &resolution);
             }
 
+            if let Some(loc) = self.drop_program.get_uniform_location("Opacity") {
+                self.drop_program.set_uniform_1f(loc, opacity);
+            }
+
             if let Some(loc) = self.drop_program.get_uniform_location("MVP") {
                 self.drop_program.set_uniform_matrix_4fv(loc, &matrix);
             }
 
+            self.background.set_layout_uniforms(&self.drop_program);
+
             if let Some(loc) = self.drop_program.get_uniform_location("Texture") {
-                self.background_texture.bind_at(0);
+                self.background.texture().bind_at(0);
                 self.drop_program.set_uniform_1i(loc, 0);
             }
 
+            Self::render_instances(
+                &self.gl,
+                &self.drop_quad,
+                self.condensation.instance_buffer(),
+                self.condensation.instance_count(),
+            );
+
             self.render_droplets(&self.gl, &self.drop_quad, &self.droplets);
         }
+
+        Self::render_particles(
+            &self.gl,
+            &self.drop_quad,
+            &self.snow_program,
+            &self.snowflakes,
+            &matrix,
+            opacity,
+        );
+        Self::render_particles(
+            &self.gl,
+            &self.drop_quad,
+            &self.hail_program,
+            &self.hail_impacts,
+            &matrix,
+            opacity,
+        );
+    }
+
+    fn render_frost(&self, frost: &Frost, matrix: &Matrix4<f32>, color: &Vector4<f32>) {
+        self.frost_program.set_used();
+
+        if let Some(loc) = self.frost_program.get_uniform_location("MVP") {
+            self.frost_program.set_uniform_matrix_4fv(loc, matrix);
+        }
+
+        if let Some(loc) = self.frost_program.get_uniform_location("Color") {
+            self.frost_program.set_uniform_4f(loc, color);
+        }
+
+        if let Some(loc) = self.frost_program.get_uniform_location("Frost") {
+            frost.texture().bind_at(0);
+            self.frost_program.set_uniform_1i(loc, 0);
+        }
+
+        self.fullscreen_quad.render(&self.gl);
+    }
+
+    fn render_particles(
+        gl: &gl::Gl,
+        quad: &Quad,
+        program: &Program,
+        particles: &Particles,
+        matrix: &Matrix4<f32>,
+        opacity: f32,
+    ) {
+        if particles.instance_count() == 0 {
+            return;
+        }
+
+        program.set_used();
+
+        if let Some(loc) = program.get_uniform_location("Opacity") {
+            program.set_uniform_1f(loc, opacity);
+        }
+
+        if let Some(loc) = program.get_uniform_location("MVP") {
+            program.set_uniform_matrix_4fv(loc, matrix);
+        }
+
+        Self::render_instances(
+            gl,
+            quad,
+            particles.instance_buffer(),
+            particles.instance_count(),
+        );
     }
 
     fn render_droplets(&self, gl: &gl::Gl, quad: &Quad, droplets: &Droplets) {
-        quad.vao.bind();
+        if let Some(gpu_droplets) = self.gpu_droplets.as_ref() {
+            gpu_droplets.render(quad);
 
-        let instance_vbo: ArrayBuffer = ArrayBuffer::new(&gl);
-        instance_vbo.bind();
+            return;
+        }
 
-        let offsets: Vec<na::Vector3<f32>> = droplets
+        let instances: Vec<DropletInstance> = droplets
             .into_iter()
             .filter(|d| !d.deleted)
-            .map(|d| na::Vector3::new(d.pos.x, d.pos.y, d.size))
+            .map(|d| DropletInstance {
+                offset: (d.pos.x, d.pos.y, d.size).into(),
+                wobble: (d.wobble_time, d.wobble_amplitude).into(),
+            })
             .collect();
 
-        instance_vbo.static_draw_data(&offsets);
+        Self::render_instance_data(gl, quad, &instances);
+    }
 
-        unsafe {
-            gl.EnableVertexAttribArray(3);
-            gl.VertexAttribPointer(
-                3,
-                3,         // the number of components per generic vertex attribute
-                gl::FLOAT, // data type
-                gl::FALSE,
-                std::mem::size_of::<na::Vector3<f32>>() as gl::types::GLint,
-                std::ptr::null(),
-            );
-        }
+    fn render_instance_data(gl: &gl::Gl, quad: &Quad, instances: &[DropletInstance]) {
+        let instance_vbo: ArrayBuffer = ArrayBuffer::new(&gl);
+        instance_vbo.bind();
+        instance_vbo.static_draw_data(instances);
+        instance_vbo.unbind();
+
+        Self::render_instances(gl, quad, &instance_vbo, instances.len());
+    }
+
+    fn render_instances(gl: &gl::Gl, quad: &Quad, instance_vbo: &ArrayBuffer, count: usize) {
+        quad.vao.bind();
+
+        instance_vbo.bind();
+        DropletInstance::vertex_attrib_pointers(gl);
         instance_vbo.unbind();
 
         unsafe {
             gl.VertexAttribDivisor(3, 1);
+            gl.VertexAttribDivisor(4, 1);
         }
 
         unsafe {
@@ -499,158 +1517,258 @@ impl Rain {
                 6,
                 gl::UNSIGNED_BYTE,
                 ::std::ptr::null(),
-                offsets.len() as i32,
+                count as i32,
             );
         }
         quad.vao.unbind();
     }
 
+    fn wobble(droplets: &mut Droplets, dt: &Duration) {
+        for droplet in droplets {
+            if droplet.deleted || droplet.wobble_amplitude <= 0.0 {
+                continue;
+            }
+
+            droplet.wobble_time += dt.as_secs_f32();
+
+            if droplet.wobble_time >= WOBBLE_DURATION {
+                droplet.wobble_amplitude = 0.0;
+                droplet.wobble_time = 0.0;
+            }
+        }
+    }
+
     fn gravity_non_linear(
         droplets: &mut Droplets,
         world: &mut CollisionWorld<f32, usize>,
-        rng: &mut ThreadRng,
+        rng: &mut ChaCha8Rng,
+        observers: &Observers,
+        wetting_mask: Option<&WettingMask>,
         dt: &Duration,
     ) {
-        let fps = 1.0 / dt.as_secs_f32();
-        let gravity_y = PRIVATE_GRAVITY_FORCE_FACTOR_Y * dt.as_secs_f32();
+        let steps = Self::integrate_all(droplets, rng.gen(), wetting_mask, dt);
 
-        for i in 0..droplets.len() {
-            let mut delete_index: Option<usize> = None;
+        // The collision world is not thread safe, so it is brought up to date serially, in index
+        // order, which also keeps the order freed droplets are reused in deterministic.
+        for (i, step) in steps.into_iter().enumerate() {
+            match step {
+                Step::Still => {}
+                Step::Moved => {
+                    let droplet = &droplets[i];
 
-            {
-                let droplet = &mut droplets[i];
+                    let object = world.get_mut(droplet.collision_handle).unwrap();
 
-                if droplet.deleted || droplet.size < DROPLET_SIZE_GRAVITY_THRESHOLD {
-                    continue;
+                    object.set_position(Isometry2::new(droplet.pos.clone_owned(), na::zero()));
                 }
+                Step::Exited => {
+                    observers.exit(&droplets[i]);
+
+                    world.remove(&[droplets[i].collision_handle]);
 
-                if droplet.size < DROPLET_SIZE_GRAVITY_THRESHOLD && droplet.seed > 0 {
-                    droplet.slowing = true;
+                    droplets.free(i);
                 }
+            }
+        }
 
-                let movement_probability = 0.01 * dt.as_secs_f64();
+        world.update();
+    }
 
-                if droplet.seed <= 0 {
-                    droplet.seed =
-                        (droplet.size * 0.5 * rng.gen_range(0.0, 1.0) * fps).floor() as i32;
-                    droplet.skipping = !droplet.skipping;
-                    droplet.slowing = true;
+    /// Advances every droplet under gravity, in parallel.
+    ///
+    /// Every droplet draws from its own stream of a generator seeded with `frame_seed`, so the
+    /// result does not depend on how the droplets are split between threads.
+    fn integrate_all(
+        droplets: &mut Droplets,
+        frame_seed: u64,
+        wetting_mask: Option<&WettingMask>,
+        dt: &Duration,
+    ) -> Vec<Step> {
+        droplets
+            .into_par_iter()
+            .enumerate()
+            .map(|(i, droplet)| {
+                // Most of the pool is usually free, and seeding a generator is not free.
+                if droplet.deleted {
+                    return Step::Still;
                 }
 
-                droplet.seed -= 1;
+                let mut rng = ChaCha8Rng::seed_from_u64(frame_seed);
+                rng.set_stream(i as u64);
 
-                assert!(droplet.size >= 1.0);
+                Self::integrate(droplet, &mut rng, wetting_mask, dt)
+            })
+            .collect()
+    }
 
-                if droplet.speed.y > 0.0 {
-                    if droplet.slowing {
-                        droplet.speed *= 0.9;
-                        if droplet.speed.y < gravity_y {
-                            droplet.slowing = false;
-                        }
-                    } else if droplet.skipping {
-                        droplet.speed.y = gravity_y;
-                        droplet.speed.x = PRIVATE_GRAVITY_FORCE_FACTOR_X;
-                    } else {
-                        droplet.speed.y += gravity_y * droplet.size;
-                        droplet.speed.x += PRIVATE_GRAVITY_FORCE_FACTOR_X * droplet.size;
-                    }
-                } else if rng.gen_bool((1.0 - 1.0 / droplet.size as f64) * movement_probability) {
-                    droplet.speed.y = gravity_y;
-                    droplet.speed.x = PRIVATE_GRAVITY_FORCE_FACTOR_X;
-                }
+    /// Advances a single live droplet under gravity, without touching anything it shares with
+    /// others.
+    fn integrate(
+        droplet: &mut Droplet,
+        rng: &mut ChaCha8Rng,
+        wetting_mask: Option<&WettingMask>,
+        dt: &Duration,
+    ) -> Step {
+        let fps = 1.0 / dt.as_secs_f32();
+        let gravity_y = PRIVATE_GRAVITY_FORCE_FACTOR_Y * dt.as_secs_f32();
+
+        // Droplets bead up and roll off water repellent glass, and cling to glass that wets easily.
+        let repellency = wetting_mask.map_or(0.0, |mask| mask.repellency(&droplet.pos));
 
-                //        if this.options.gravityAngleVariance != 0 {
-                //            droplet.x_speed +=
-                //                (rnd.gen() * 2 - 1) * droplet.y_speed * this.options.gravityAngleVariance
-                //        }
+        let gravity_threshold =
+            DROPLET_SIZE_GRAVITY_THRESHOLD * (1.0 - WETTING_GRAVITY_THRESHOLD_FACTOR * repellency);
 
-                droplet.pos.y -= droplet.speed.y;
-                droplet.pos.x += droplet.speed.x;
+        if droplet.size < gravity_threshold {
+            return Step::Still;
+        }
 
-                if droplet.pos.y + droplet.size * 0.5 < 0.0 {
-                    delete_index = Some(i);
+        if droplet.size < DROPLET_SIZE_GRAVITY_THRESHOLD && droplet.seed > 0 {
+            droplet.slowing = true;
+        }
 
-                    world.remove(&[droplet.collision_handle]);
-                } else if droplet.speed.x != 0.0 || droplet.speed.y != 0.0 {
-                    let handle = droplet.collision_handle;
+        let movement_probability =
+            (0.01 * dt.as_secs_f64() * WETTING_MOBILITY.powf(repellency) as f64).min(1.0);
 
-                    let object = world.get_mut(handle).unwrap();
+        if droplet.seed <= 0 {
+            droplet.seed = (droplet.size * 0.5 * rng.gen_range(0.0, 1.0) * fps).floor() as i32;
+            droplet.skipping = !droplet.skipping;
+            droplet.slowing = true;
+        }
 
-                    object.set_position(Isometry2::new(droplet.pos.clone_owned(), na::zero()));
-                }
-            }
+        droplet.seed -= 1;
+
+        assert!(droplet.size >= 1.0);
 
-            if let Some(delete_index) = delete_index {
-                droplets.free(delete_index);
+        if droplet.speed.y > 0.0 {
+            if droplet.slowing {
+                droplet.speed *= 0.9;
+                if droplet.speed.y < gravity_y {
+                    droplet.slowing = false;
+                }
+            } else if droplet.skipping {
+                droplet.speed.y = gravity_y;
+                droplet.speed.x = PRIVATE_GRAVITY_FORCE_FACTOR_X;
+            } else {
+                droplet.speed.y += gravity_y * droplet.size;
+                droplet.speed.x += PRIVATE_GRAVITY_FORCE_FACTOR_X * droplet.size;
             }
+        } else if rng.gen_bool((1.0 - 1.0 / droplet.size as f64) * movement_probability) {
+            droplet.speed.y = gravity_y;
+            droplet.speed.x = PRIVATE_GRAVITY_FORCE_FACTOR_X;
         }
 
-        world.update();
+        //        if this.options.gravityAngleVariance != 0 {
+        //            droplet.x_speed +=
+        //                (rnd.gen() * 2 - 1) * droplet.y_speed * this.options.gravityAngleVariance
+        //        }
+
+        droplet.pos.y -= droplet.speed.y;
+        droplet.pos.x += droplet.speed.x;
+
+        if droplet.pos.y + droplet.size * 0.5 < 0.0 {
+            Step::Exited
+        } else if droplet.speed.x != 0.0 || droplet.speed.y != 0.0 {
+            Step::Moved
+        } else {
+            Step::Still
+        }
     }
 
-    fn trail(
+    /// Lets running droplets leave some of their water behind as streaks.
+    fn streak(
         droplets: &mut Droplets,
         world: &mut CollisionWorld<f32, usize>,
-        rng: &mut ThreadRng,
-        collision_group: &CollisionGroups,
-        contacts_query: &GeometricQueryType<f32>,
+        rng: &mut ChaCha8Rng,
+        streaks: &mut Streaks,
         dt: &Duration,
     ) {
         let gravity_y = PRIVATE_GRAVITY_FORCE_FACTOR_Y * dt.as_secs_f32();
 
+        let mut extended = HashSet::new();
+
         for i in 0..droplets.len() {
-            let pos;
-            let size;
+            let droplet = &mut droplets[i];
 
+            if droplet.deleted
+                || droplet.speed.y <= gravity_y
+                || droplet.size < STREAK_SIZE_THRESHOLD
             {
-                let droplet = &mut droplets[i];
+                continue;
+            }
 
-                if droplet.speed.y <= gravity_y {
-                    continue;
-                }
+            let volume = (droplet.size * 0.5).powf(3.0);
+            let deposit = volume * (STREAK_DEPOSIT_RATE * droplet.speed.norm()).min(0.5);
 
-                if droplet.size >= 6.0
-                    && (droplet.last_trail_y.is_none()
-                        || (droplet.last_trail_y.unwrap_or(0.0) - droplet.pos.y)
-                            >= rng.gen_range(0.1, 1.0) * 200.0)
-                {
-                    droplet.last_trail_y = Some(droplet.pos.y);
+            droplet.size = (volume - deposit).cbrt() * 2.0;
 
-                    size = rng.gen_range(0.9, 1.1) * droplet.size * 0.25;
-                    pos = Vector2::new(
-                        droplet.pos.x + rng.gen_range(-1.0, 1.0),
-                        droplet.pos.y + droplet.size * 0.5 + droplet.speed.y + size * 0.5,
-                    );
+            if let Some(droplet_collision) = world.get_mut(droplet.collision_handle) {
+                droplet_collision.set_shape(ShapeHandle::new(Ball::new(droplet.size * 0.5)))
+            }
 
-                    droplet.size =
-                        ((droplet.size * 0.5).powf(3.0) - (size * 0.5).powf(3.0)).cbrt() * 2.0;
+            streaks.extend(rng, i, droplet, deposit);
 
-                    if let Some(droplet_collision) = world.get_mut(droplet.collision_handle) {
-                        droplet_collision.set_shape(ShapeHandle::new(Ball::new(droplet.size * 0.5)))
-                    }
-                } else {
-                    continue;
-                }
-            }
+            extended.insert(i);
+        }
 
-            if let Some((i, d)) = droplets.checkout() {
-                d.pos = pos;
-                d.size = size;
+        streaks.detach_idle(rng, &extended);
+    }
+}
 
-                let shape_handle = ShapeHandle::new(Ball::new(d.size * 0.5));
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-                let handle = world
-                    .add(
-                        Isometry2::new(d.pos.clone_owned(), na::zero()),
-                        shape_handle,
-                        *collision_group,
-                        *contacts_query,
-                        i,
-                    )
-                    .0;
+    // Enough droplets for rayon to split them between threads.
+    const CAPACITY: usize = 4096;
 
-                d.collision_handle = handle;
-            }
+    fn droplets() -> Droplets {
+        let mut rng = ChaCha8Rng::seed_from_u64(7);
+        let mut droplets = Droplets::with_capacity(CAPACITY);
+
+        // Leaves every fourth slot free.
+        for _ in 0..CAPACITY * 3 / 4 {
+            let (_, droplet) = droplets.checkout().unwrap();
+
+            droplet.pos = Vector2::new(rng.gen_range(0.0, 1920.0), rng.gen_range(0.0, 1080.0));
+            droplet.size = rng.gen_range(1.0, 20.0);
+            droplet.speed = Vector2::new(0.0, -rng.gen_range(0.0, 4.0));
+            droplet.seed = rng.gen_range(-2, 3);
+        }
+
+        droplets
+    }
+
+    fn integrate_on_threads(threads: usize) -> (Vec<Step>, Droplets) {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .unwrap();
+
+        let mut droplets = droplets();
+        let dt = Duration::from_nanos(1_000_000_000 / 60);
+
+        let steps = pool.install(|| {
+            (0..30)
+                .flat_map(|frame| Rain::integrate_all(&mut droplets, frame, None, &dt))
+                .collect()
+        });
+
+        (steps, droplets)
+    }
+
+    #[test]
+    fn integration_does_not_depend_on_thread_count() {
+        let (single_steps, single) = integrate_on_threads(1);
+        let (parallel_steps, parallel) = integrate_on_threads(8);
+
+        assert_eq!(single_steps, parallel_steps);
+        assert!(single_steps.contains(&Step::Moved));
+
+        for (a, b) in single.into_iter().zip(&parallel) {
+            assert_eq!(a.pos, b.pos);
+            assert_eq!(a.speed, b.speed);
+            assert_eq!(a.seed, b.seed);
+            assert_eq!(a.skipping, b.skipping);
+            assert_eq!(a.slowing, b.slowing);
         }
     }
 }
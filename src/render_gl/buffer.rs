@@ -12,6 +12,11 @@ impl BufferType for BufferTypeElementArray {
     const BUFFER_TYPE: gl::types::GLuint = gl::ELEMENT_ARRAY_BUFFER;
 }
 
+pub struct BufferTypeShaderStorage;
+impl BufferType for BufferTypeShaderStorage {
+    const BUFFER_TYPE: gl::types::GLuint = gl::SHADER_STORAGE_BUFFER;
+}
+
 pub struct Buffer<B>
 where
     B: BufferType,
@@ -50,6 +55,26 @@ where
         }
     }
 
+    /// Binds the buffer to an indexed binding point of its target, e.g. a shader storage block.
+    pub fn bind_base(&self, index: gl::types::GLuint) {
+        unsafe {
+            self.gl.BindBufferBase(B::BUFFER_TYPE, index, self.vbo);
+        }
+    }
+
+    /// Binds the buffer to another target, e.g. to feed vertex attributes from a storage buffer.
+    pub fn bind_as<T: BufferType>(&self) {
+        unsafe {
+            self.gl.BindBuffer(T::BUFFER_TYPE, self.vbo);
+        }
+    }
+
+    pub fn unbind_as<T: BufferType>(&self) {
+        unsafe {
+            self.gl.BindBuffer(T::BUFFER_TYPE, 0);
+        }
+    }
+
     pub fn static_draw_data<T>(&self, data: &[T]) {
         unsafe {
             self.gl.BufferData(
@@ -83,6 +108,46 @@ where
         }
     }
 
+    pub fn sub_data<T>(&self, offset: usize, data: &[T]) {
+        unsafe {
+            self.gl.BufferSubData(
+                B::BUFFER_TYPE,                                                     // target
+                (offset * ::std::mem::size_of::<T>()) as gl::types::GLintptr, // offset in bytes
+                (data.len() * ::std::mem::size_of::<T>()) as gl::types::GLsizeiptr, // size of data in bytes
+                data.as_ptr() as *const gl::types::GLvoid, // pointer to data
+            );
+        }
+    }
+
+    /// Reads `len` elements back from the GPU, starting at `offset`.
+    pub fn get_sub_data<T: Copy + Default>(&self, offset: usize, len: usize) -> Vec<T> {
+        let mut data = vec![T::default(); len];
+
+        unsafe {
+            self.gl.GetBufferSubData(
+                B::BUFFER_TYPE,                                                     // target
+                (offset * ::std::mem::size_of::<T>()) as gl::types::GLintptr, // offset in bytes
+                (data.len() * ::std::mem::size_of::<T>()) as gl::types::GLsizeiptr, // size of data in bytes
+                data.as_mut_ptr() as *mut gl::types::GLvoid, // pointer to data
+            );
+        }
+
+        data
+    }
+
+    /// Fills the whole buffer with a repeated 32-bit integer.
+    pub fn clear_i32(&self, value: i32) {
+        unsafe {
+            self.gl.ClearBufferData(
+                B::BUFFER_TYPE,
+                gl::R32I,
+                gl::RED_INTEGER,
+                gl::INT,
+                &value as *const i32 as *const gl::types::GLvoid,
+            );
+        }
+    }
+
     pub unsafe fn map_buffer_range_write_invalidate<'r, T>(
         &self,
         offset: usize,
@@ -159,6 +224,7 @@ where
 
 pub type ArrayBuffer = Buffer<BufferTypeArray>;
 pub type ElementArrayBuffer = Buffer<BufferTypeElementArray>;
+pub type ShaderStorageBuffer = Buffer<BufferTypeShaderStorage>;
 
 pub struct VertexArray {
     gl: gl::Gl,
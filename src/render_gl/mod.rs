@@ -8,6 +8,6 @@ mod viewport;
 
 pub use self::color_buffer::ColorBuffer;
 pub use self::framebuffer::FrameBuffer;
-pub use self::shader::{Error, Program, Shader};
+pub use self::shader::{with_defines, with_prelude, Error, Program, Shader};
 pub use self::texture::{Texture, TextureLoadOptions};
 pub use self::viewport::Viewport;
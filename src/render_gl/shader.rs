@@ -109,6 +109,20 @@ impl Program {
         }
     }
 
+    pub fn set_uniform_2fv(&self, location: i32, value: &[[f32; 2]]) {
+        unsafe {
+            self.gl
+                .Uniform2fv(location, value.len() as i32, value.as_ptr() as *const f32);
+        }
+    }
+
+    pub fn set_uniform_1fv(&self, location: i32, value: &[f32]) {
+        unsafe {
+            self.gl
+                .Uniform1fv(location, value.len() as i32, value.as_ptr());
+        }
+    }
+
     pub fn set_uniform_4f(&self, location: i32, value: &na::Vector4<f32>) {
         unsafe {
             self.gl
@@ -128,6 +142,12 @@ impl Program {
         }
     }
 
+    pub fn set_uniform_2i(&self, location: i32, x: i32, y: i32) {
+        unsafe {
+            self.gl.Uniform2i(location, x, y);
+        }
+    }
+
     pub fn set_uniform_1f(&self, location: i32, value: f32) {
         unsafe {
             self.gl.Uniform1f(location, value);
@@ -179,6 +199,15 @@ impl Shader {
         })
     }
 
+    pub fn from_compute_source_str(gl: &gl::Gl, source: &str) -> Result<Shader, Error> {
+        Shader::from_source(gl, &str_to_cstr(source), gl::COMPUTE_SHADER).map_err(|msg| {
+            Error::CompileError {
+                message: msg,
+                name: "".into(),
+            }
+        })
+    }
+
     pub fn from_vert_source(gl: &gl::Gl, source: &CStr) -> Result<Shader, String> {
         Shader::from_source(gl, source, gl::VERTEX_SHADER)
     }
@@ -251,6 +280,48 @@ fn create_whitespace_cstring_with_len(len: usize) -> CString {
     unsafe { CString::from_vec_unchecked(buffer) }
 }
 
+/// Inserts `#define`s right after the `#version` line of a shader source, which has to come first.
+pub fn with_defines(source: &str, defines: &[(&str, String)]) -> String {
+    let defines: String = defines
+        .iter()
+        .map(|(name, value)| format!("#define {} {}\n", name, value))
+        .collect();
+
+    after_version(source, &defines)
+}
+
+/// Inserts code shared by several shaders, such as uniforms and functions, right after the
+/// `#version` line of a shader source.
+pub fn with_prelude(source: &str, prelude: &str) -> String {
+    after_version(source, prelude.trim_start_matches('\u{feff}'))
+}
+
+fn after_version(source: &str, inserted: &str) -> String {
+    let source = source.trim_start_matches('\u{feff}');
+
+    let (version, rest) = match source.find('\n') {
+        Some(end) => source.split_at(end + 1),
+        None => (source, ""),
+    };
+
+    let mut result = version.to_string();
+
+    result.push_str(inserted);
+
+    if !inserted.is_empty() && !inserted.ends_with('\n') {
+        result.push('\n');
+    }
+
+    // Keeps the line numbers in compile errors matching the file.
+    result.push_str("#line 2\n");
+    result.push_str(rest);
+
+    result
+}
+
 fn str_to_cstr(str: &str) -> std::ffi::CString {
+    // Sources saved with a byte order mark are rejected by stricter drivers, such as Mesa's.
+    let str = str.trim_start_matches('\u{feff}');
+
     unsafe { std::ffi::CString::from_vec_unchecked(str.as_bytes().to_vec()) }
 }
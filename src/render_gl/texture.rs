@@ -1,8 +1,24 @@
+use image::GenericImageView;
 use std::os::raw;
 
+#[derive(Debug, Fail)]
+pub enum Error {
+    #[fail(
+        display = "Image of {}x{} is larger than the maximum texture size of {}",
+        width, height, max_size
+    )]
+    TooLarge {
+        width: u32,
+        height: u32,
+        max_size: u32,
+    },
+}
+
 pub struct TextureLoadOptions {
     format: gl::types::GLenum,
     pub gen_mipmaps: bool,
+    /// Stores the image as sRGB, so that shaders sample it as linear colours.
+    pub srgb: bool,
 }
 
 impl TextureLoadOptions {
@@ -10,6 +26,7 @@ impl TextureLoadOptions {
         TextureLoadOptions {
             format: gl::RGB,
             gen_mipmaps: false,
+            srgb: false,
         }
     }
 
@@ -17,6 +34,7 @@ impl TextureLoadOptions {
         TextureLoadOptions {
             format: gl::RGBA,
             gen_mipmaps: false,
+            srgb: false,
         }
     }
 }
@@ -36,6 +54,21 @@ impl Drop for Texture {
 
 impl Texture {
     pub fn new(gl: &gl::Gl, width: u32, height: u32) -> Result<Texture, failure::Error> {
+        Self::new_with_format(gl, width, height, gl::RGBA16)
+    }
+
+    pub fn new_with_format(
+        gl: &gl::Gl,
+        width: u32,
+        height: u32,
+        internal_format: gl::types::GLenum,
+    ) -> Result<Texture, failure::Error> {
+        let (format, data_type) = match internal_format {
+            gl::R16F | gl::R32F => (gl::RED, gl::FLOAT),
+            gl::RGBA16F | gl::RGBA32F => (gl::RGBA, gl::FLOAT),
+            _ => (gl::RGBA, gl::UNSIGNED_BYTE),
+        };
+
         let mut obj: gl::types::GLuint = 0;
         unsafe {
             gl.GenTextures(1, &mut obj);
@@ -68,12 +101,12 @@ impl Texture {
             gl.TexImage2D(
                 gl::TEXTURE_2D,
                 0,
-                gl::RGBA16 as gl::types::GLint,
+                internal_format as gl::types::GLint,
                 width as i32,
                 height as i32,
                 0,
-                gl::RGBA,
-                gl::UNSIGNED_BYTE,
+                format,
+                data_type,
                 std::ptr::null(),
             );
         }
@@ -90,6 +123,18 @@ impl Texture {
         gl: &gl::Gl,
         image: &image::DynamicImage,
     ) -> Result<Texture, failure::Error> {
+        let (width, height) = image.dimensions();
+        let max_size = Self::max_size(gl);
+
+        if width > max_size || height > max_size {
+            return Err(Error::TooLarge {
+                width,
+                height,
+                max_size,
+            }
+            .into());
+        }
+
         let mut obj: gl::types::GLuint = 0;
         unsafe {
             gl.GenTextures(1, &mut obj);
@@ -107,10 +152,15 @@ impl Texture {
         }
 
         // https://www.khronos.org/opengl/wiki/Common_Mistakes
+        // Rows of RGB images are not padded to 4 bytes.
+        unsafe {
+            gl.PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+        }
 
         match options.format {
             gl::RGB => {
-                let img = image.flipv().to_rgb();
+                let img = image.flipv().to_rgb8();
+                let internal_format = if options.srgb { gl::SRGB8 } else { gl::RGB8 };
 
                 let dims = img.dimensions();
 
@@ -122,7 +172,7 @@ impl Texture {
                         gl.TexImage2D(
                             gl::TEXTURE_2D,
                             0,
-                            gl::RGB8 as gl::types::GLint,
+                            internal_format as gl::types::GLint,
                             texture.width as i32,
                             texture.height as i32,
                             0,
@@ -139,7 +189,7 @@ impl Texture {
                         gl.TexImage2D(
                             gl::TEXTURE_2D,
                             0,
-                            gl::RGB8 as gl::types::GLint,
+                            internal_format as gl::types::GLint,
                             texture.width as i32,
                             texture.height as i32,
                             0,
@@ -151,7 +201,12 @@ impl Texture {
                 }
             }
             gl::RGBA => {
-                let img = image.flipv().to_rgba();
+                let img = image.flipv().to_rgba8();
+                let internal_format = if options.srgb {
+                    gl::SRGB8_ALPHA8
+                } else {
+                    gl::RGBA8
+                };
 
                 let dims = img.dimensions();
 
@@ -163,7 +218,7 @@ impl Texture {
                         gl.TexImage2D(
                             gl::TEXTURE_2D,
                             0,
-                            gl::RGBA8 as gl::types::GLint,
+                            internal_format as gl::types::GLint,
                             texture.width as i32,
                             texture.height as i32,
                             0,
@@ -180,7 +235,7 @@ impl Texture {
                         gl.TexImage2D(
                             gl::TEXTURE_2D,
                             0,
-                            gl::RGBA8 as gl::types::GLint,
+                            internal_format as gl::types::GLint,
                             texture.width as i32,
                             texture.height as i32,
                             0,
@@ -195,12 +250,24 @@ impl Texture {
         }
 
         unsafe {
+            gl.PixelStorei(gl::UNPACK_ALIGNMENT, 4);
             gl.BindTexture(gl::TEXTURE_2D, 0);
         }
 
         Ok(texture)
     }
 
+    /// The largest width or height of a texture the driver supports.
+    pub fn max_size(gl: &gl::Gl) -> u32 {
+        let mut max_size: gl::types::GLint = 0;
+
+        unsafe {
+            gl.GetIntegerv(gl::MAX_TEXTURE_SIZE, &mut max_size);
+        }
+
+        max_size as u32
+    }
+
     pub fn bind(&self) {
         unsafe {
             self.gl.BindTexture(gl::TEXTURE_2D, self.obj);
@@ -220,6 +287,122 @@ impl Texture {
         self.bind();
     }
 
+    /// Interpolates between pixels also where the texture is drawn smaller than it is.
+    pub fn set_linear_filtering(&self) {
+        self.bind();
+
+        unsafe {
+            self.gl.TexParameteri(
+                gl::TEXTURE_2D,
+                gl::TEXTURE_MIN_FILTER,
+                gl::LINEAR as gl::types::GLint,
+            );
+            self.gl.TexParameteri(
+                gl::TEXTURE_2D,
+                gl::TEXTURE_MAG_FILTER,
+                gl::LINEAR as gl::types::GLint,
+            );
+        }
+
+        self.unbind();
+    }
+
+    /// Fills the smaller levels of the texture in from the first one, and interpolates between
+    /// them when sampling.
+    pub fn generate_mipmaps(&self) {
+        self.bind();
+
+        unsafe {
+            self.gl
+                .TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAX_LEVEL, 1000);
+            self.gl.TexParameteri(
+                gl::TEXTURE_2D,
+                gl::TEXTURE_MIN_FILTER,
+                gl::LINEAR_MIPMAP_LINEAR as gl::types::GLint,
+            );
+            self.gl.GenerateMipmap(gl::TEXTURE_2D);
+        }
+
+        self.unbind();
+    }
+
+    /// Reads the red channel back from the GPU, e.g. to save a mask to disk.
+    pub fn to_luma_image(&self) -> image::GrayImage {
+        let mut data = vec![0u8; (self.width * self.height) as usize];
+
+        self.bind();
+
+        unsafe {
+            self.gl.PixelStorei(gl::PACK_ALIGNMENT, 1);
+            self.gl.GetTexImage(
+                gl::TEXTURE_2D,
+                0,
+                gl::RED,
+                gl::UNSIGNED_BYTE,
+                data.as_mut_ptr() as *mut raw::c_void,
+            );
+            self.gl.PixelStorei(gl::PACK_ALIGNMENT, 4);
+        }
+
+        self.unbind();
+
+        let image = image::GrayImage::from_raw(self.width, self.height, data).unwrap();
+
+        image::imageops::flip_vertical(&image)
+    }
+
+    /// Replaces the red channel with the given image, which must match the texture's dimensions.
+    pub fn update_from_luma_image(&self, image: &image::GrayImage) {
+        assert_eq!(image.dimensions(), self.dimensions());
+
+        let img = image::imageops::flip_vertical(image);
+
+        self.bind();
+
+        unsafe {
+            self.gl.PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+            self.gl.TexSubImage2D(
+                gl::TEXTURE_2D,
+                0,
+                0,
+                0,
+                self.width as i32,
+                self.height as i32,
+                gl::RED,
+                gl::UNSIGNED_BYTE,
+                img.as_ptr() as *const raw::c_void,
+            );
+            self.gl.PixelStorei(gl::UNPACK_ALIGNMENT, 4);
+        }
+
+        self.unbind();
+    }
+
+    /// Replaces the whole texture with the given image, which must match its dimensions.
+    pub fn update_from_rgba_image(&self, image: &image::RgbaImage) {
+        assert_eq!(image.dimensions(), self.dimensions());
+
+        let img = image::imageops::flip_vertical(image);
+
+        self.bind();
+
+        unsafe {
+            self.gl.TexSubImage2D(
+                gl::TEXTURE_2D,
+                0,
+                0,
+                0,
+                self.width as i32,
+                self.height as i32,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                img.as_ptr() as *const raw::c_void,
+            );
+        }
+
+        self.unbind();
+    }
+
     pub fn dimensions(&self) -> (u32, u32) {
         (self.width, self.height)
     }
@@ -0,0 +1,374 @@
+﻿use crate::binary::*;
+use crate::config::{Precipitation, Simulation};
+use nalgebra::Vector2;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::time::Duration;
+
+const MAGIC: &[u8; 8] = b"RAINPLAY";
+// Bumped whenever the simulation draws from its generator differently or the header changes,
+// since older replays would no longer reproduce.
+const VERSION: u32 = 6;
+
+#[derive(Debug, Fail)]
+pub enum Error {
+    #[fail(display = "Not a replay file")]
+    InvalidFormat,
+    #[fail(display = "Unsupported replay version {}", version)]
+    UnsupportedVersion { version: u32 },
+    #[fail(display = "Unknown precipitation {}", precipitation)]
+    UnknownPrecipitation { precipitation: u64 },
+    #[fail(display = "Unknown simulation {}", simulation)]
+    UnknownSimulation { simulation: u64 },
+}
+
+/// Everything that influences the simulation, apart from the frames themselves.
+pub struct ReplayHeader {
+    pub seed: u64,
+    pub resolution: (u32, u32),
+    pub max_droplet_count: usize,
+    pub droplet_size_range: (f32, f32),
+    pub simulation: Simulation,
+    pub precipitation: Precipitation,
+    /// The fingerprint of the wetting mask the droplets were steered by, if there was one.
+    pub wetting_mask: Option<u64>,
+}
+
+/// A single call to `Rain::update`, along with the droplets spawned by the user right before it.
+pub struct ReplayFrame {
+    pub delta: Duration,
+    pub spawns: Vec<Vector2<f32>>,
+}
+
+/// A recorded run, which reproduces the simulation exactly when fed back to a `Rain` created
+/// with the same header.
+pub struct Replay {
+    pub header: ReplayHeader,
+    pub frames: Vec<ReplayFrame>,
+}
+
+impl Replay {
+    pub fn new(header: ReplayHeader) -> Self {
+        Replay {
+            header,
+            frames: Vec::new(),
+        }
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.frames.iter().map(|frame| frame.delta).sum()
+    }
+
+    pub fn spawn_count(&self) -> usize {
+        self.frames.iter().map(|frame| frame.spawns.len()).sum()
+    }
+
+    pub fn load(path: &Path) -> Result<Replay, failure::Error> {
+        let mut reader = BufReader::new(File::open(path)?);
+
+        Self::read(&mut reader)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), failure::Error> {
+        let mut writer = BufWriter::new(File::create(path)?);
+
+        self.write(&mut writer)?;
+
+        writer.flush()?;
+
+        Ok(())
+    }
+
+    pub fn read<R: Read>(reader: &mut R) -> Result<Replay, failure::Error> {
+        let mut magic = [0u8; 8];
+        reader.read_exact(&mut magic)?;
+
+        if &magic != MAGIC {
+            return Err(Error::InvalidFormat.into());
+        }
+
+        let version = read_u32(reader)?;
+
+        if version != VERSION {
+            return Err(Error::UnsupportedVersion { version }.into());
+        }
+
+        let header = ReplayHeader {
+            seed: read_u64(reader)?,
+            resolution: (read_u32(reader)?, read_u32(reader)?),
+            max_droplet_count: read_varint(reader)? as usize,
+            droplet_size_range: (read_f32(reader)?, read_f32(reader)?),
+            simulation: read_simulation(reader)?,
+            precipitation: read_precipitation(reader)?,
+            wetting_mask: if read_bool(reader)? {
+                Some(read_u64(reader)?)
+            } else {
+                None
+            },
+        };
+
+        let frame_count = read_varint(reader)? as usize;
+        let frames = (0..frame_count)
+            .map(|_| read_frame(reader))
+            .collect::<io::Result<Vec<_>>>()?;
+
+        Ok(Replay { header, frames })
+    }
+
+    pub fn write<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(MAGIC)?;
+        write_u32(writer, VERSION)?;
+
+        write_u64(writer, self.header.seed)?;
+        write_u32(writer, self.header.resolution.0)?;
+        write_u32(writer, self.header.resolution.1)?;
+        write_varint(writer, self.header.max_droplet_count as u64)?;
+        write_f32(writer, self.header.droplet_size_range.0)?;
+        write_f32(writer, self.header.droplet_size_range.1)?;
+        write_simulation(writer, self.header.simulation)?;
+        write_precipitation(writer, self.header.precipitation)?;
+        write_bool(writer, self.header.wetting_mask.is_some())?;
+        if let Some(wetting_mask) = self.header.wetting_mask {
+            write_u64(writer, wetting_mask)?;
+        }
+
+        write_varint(writer, self.frames.len() as u64)?;
+        for frame in self.frames.iter() {
+            write_frame(writer, frame)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn read_simulation<R: Read>(reader: &mut R) -> Result<Simulation, failure::Error> {
+    match read_varint(reader)? {
+        0 => Ok(Simulation::Cpu),
+        1 => Ok(Simulation::Gpu),
+        simulation => Err(Error::UnknownSimulation { simulation }.into()),
+    }
+}
+
+fn write_simulation<W: Write>(writer: &mut W, simulation: Simulation) -> io::Result<()> {
+    let simulation = match simulation {
+        Simulation::Cpu => 0,
+        Simulation::Gpu => 1,
+    };
+
+    write_varint(writer, simulation)
+}
+
+fn read_precipitation<R: Read>(reader: &mut R) -> Result<Precipitation, failure::Error> {
+    match read_varint(reader)? {
+        0 => Ok(Precipitation::Rain),
+        1 => Ok(Precipitation::Snow),
+        2 => Ok(Precipitation::Sleet),
+        3 => Ok(Precipitation::Hail),
+        precipitation => Err(Error::UnknownPrecipitation { precipitation }.into()),
+    }
+}
+
+fn write_precipitation<W: Write>(writer: &mut W, precipitation: Precipitation) -> io::Result<()> {
+    let precipitation = match precipitation {
+        Precipitation::Rain => 0,
+        Precipitation::Snow => 1,
+        Precipitation::Sleet => 2,
+        Precipitation::Hail => 3,
+    };
+
+    write_varint(writer, precipitation)
+}
+
+fn read_frame<R: Read>(reader: &mut R) -> io::Result<ReplayFrame> {
+    let delta = Duration::from_nanos(read_varint(reader)?);
+
+    let spawn_count = read_varint(reader)? as usize;
+    let spawns = (0..spawn_count)
+        .map(|_| Ok(Vector2::new(read_f32(reader)?, read_f32(reader)?)))
+        .collect::<io::Result<Vec<_>>>()?;
+
+    Ok(ReplayFrame { delta, spawns })
+}
+
+fn write_frame<W: Write>(writer: &mut W, frame: &ReplayFrame) -> io::Result<()> {
+    // Frame times are stored to the nanosecond, since rounding them would change the simulation.
+    write_varint(writer, frame.delta.as_nanos() as u64)?;
+
+    write_varint(writer, frame.spawns.len() as u64)?;
+    for spawn in frame.spawns.iter() {
+        write_f32(writer, spawn.x)?;
+        write_f32(writer, spawn.y)?;
+    }
+
+    Ok(())
+}
+
+/// Collects frames while the simulation runs.
+pub struct Recorder {
+    replay: Replay,
+    spawns: Vec<Vector2<f32>>,
+}
+
+impl Recorder {
+    pub fn new(header: ReplayHeader) -> Self {
+        Recorder {
+            replay: Replay::new(header),
+            spawns: Vec::new(),
+        }
+    }
+
+    /// Records a droplet spawned by the user, to be replayed right before the next frame.
+    pub fn spawn(&mut self, pos: Vector2<f32>) {
+        self.spawns.push(pos);
+    }
+
+    pub fn frame(&mut self, delta: Duration) {
+        self.replay.frames.push(ReplayFrame {
+            delta,
+            spawns: std::mem::take(&mut self.spawns),
+        });
+    }
+
+    pub fn replay(&self) -> &Replay {
+        &self.replay
+    }
+}
+
+/// Hands out the frames of a replay in order.
+pub struct Player {
+    replay: Replay,
+    position: usize,
+}
+
+impl Player {
+    pub fn new(replay: Replay) -> Self {
+        Player {
+            replay,
+            position: 0,
+        }
+    }
+
+    pub fn header(&self) -> &ReplayHeader {
+        &self.replay.header
+    }
+
+    /// The index of the next frame to be played.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    pub fn next_frame(&mut self) -> Option<&ReplayFrame> {
+        let frame = self.replay.frames.get(self.position)?;
+
+        self.position += 1;
+
+        Some(frame)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn replay(wetting_mask: Option<u64>) -> Replay {
+        let mut replay = Replay::new(ReplayHeader {
+            seed: 0x0123_4567_89ab_cdef,
+            resolution: (1920, 1080),
+            max_droplet_count: 10_000,
+            droplet_size_range: (2.0, 18.5),
+            simulation: Simulation::Gpu,
+            precipitation: Precipitation::Sleet,
+            wetting_mask,
+        });
+
+        replay.frames.push(ReplayFrame {
+            delta: Duration::new(0, 16_666_667),
+            spawns: vec![],
+        });
+        replay.frames.push(ReplayFrame {
+            delta: Duration::from_nanos(1),
+            spawns: vec![Vector2::new(12.25, -3.5), Vector2::new(1919.0, 0.0)],
+        });
+        // Takes more than a single byte of varint.
+        replay.frames.push(ReplayFrame {
+            delta: Duration::new(3, 999_999_999),
+            spawns: vec![Vector2::new(0.1, 0.2); 200],
+        });
+
+        replay
+    }
+
+    fn bytes(replay: &Replay) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        replay.write(&mut bytes).unwrap();
+
+        bytes
+    }
+
+    fn read_error(bytes: &[u8]) -> failure::Error {
+        match Replay::read(&mut &bytes[..]) {
+            Ok(_) => panic!("the replay was read"),
+            Err(e) => e,
+        }
+    }
+
+    #[test]
+    fn replay_round_trips() {
+        for &wetting_mask in [None, Some(0xfeed_f00d_dead_beef)].iter() {
+            let original = replay(wetting_mask);
+            let read = Replay::read(&mut bytes(&original).as_slice()).unwrap();
+
+            let header = &read.header;
+
+            assert_eq!(header.seed, original.header.seed);
+            assert_eq!(header.resolution, original.header.resolution);
+            assert_eq!(header.max_droplet_count, original.header.max_droplet_count);
+            assert_eq!(
+                header.droplet_size_range,
+                original.header.droplet_size_range
+            );
+            assert_eq!(header.simulation, original.header.simulation);
+            assert_eq!(header.precipitation, original.header.precipitation);
+            assert_eq!(header.wetting_mask, wetting_mask);
+
+            assert_eq!(read.frames.len(), original.frames.len());
+
+            for (read, original) in read.frames.iter().zip(original.frames.iter()) {
+                assert_eq!(read.delta, original.delta);
+                assert_eq!(read.spawns, original.spawns);
+            }
+
+            assert_eq!(bytes(&read), bytes(&original));
+        }
+    }
+
+    #[test]
+    fn bad_magic_is_rejected() {
+        let mut bytes = bytes(&replay(None));
+        bytes[0] = b'X';
+
+        assert!(matches!(
+            read_error(&bytes).downcast_ref::<Error>(),
+            Some(Error::InvalidFormat)
+        ));
+    }
+
+    #[test]
+    fn other_version_is_rejected() {
+        let mut bytes = bytes(&replay(None));
+        bytes[MAGIC.len()..MAGIC.len() + 4].copy_from_slice(&(VERSION - 1).to_le_bytes());
+
+        assert!(matches!(
+            read_error(&bytes).downcast_ref::<Error>(),
+            Some(Error::UnsupportedVersion { version }) if *version == VERSION - 1
+        ));
+    }
+
+    #[test]
+    fn truncated_replay_is_rejected() {
+        let bytes = bytes(&replay(None));
+
+        read_error(&bytes[..bytes.len() - 1]);
+    }
+}
@@ -0,0 +1,219 @@
+﻿use crate::background_loader::{self, SizeLimit};
+use crate::config::SlideshowOrder;
+use rand::seq::SliceRandom;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::thread;
+use std::time::Duration;
+
+// Files in a directory with any other extension are skipped.
+const IMAGE_EXTENSIONS: [&str; 9] = [
+    "jpg", "jpeg", "png", "bmp", "gif", "tif", "tiff", "webp", "tga",
+];
+
+// A text file listing one image per line, relative to the playlist.
+const PLAYLIST_EXTENSIONS: [&str; 3] = ["txt", "m3u", "m3u8"];
+
+#[derive(Debug, Fail)]
+pub enum Error {
+    #[fail(display = "No images in {}", path)]
+    NoImages { path: String },
+}
+
+/// Whether `path` is a directory or a playlist of backgrounds rather than a single image.
+pub fn is_slideshow(path: &Path) -> bool {
+    path.is_dir() || has_extension(path, &PLAYLIST_EXTENSIONS)
+}
+
+/// Whether `path` has the extension of an image a slideshow shows.
+pub fn is_image(path: &Path) -> bool {
+    has_extension(path, &IMAGE_EXTENSIONS)
+}
+
+/// Backgrounds from a directory or playlist, shown one after the other.
+///
+/// The next image is decoded on a worker thread while the current one is shown, so switching
+/// never waits for a file to load. If the next image is not ready yet when its time comes, the
+/// current one simply stays a little longer.
+pub struct Slideshow {
+    paths: Vec<PathBuf>,
+    order: SlideshowOrder,
+    // Index of the image shown, in `paths`.
+    current: usize,
+    interval: f32,
+    elapsed: f32,
+
+    requests: Sender<PathBuf>,
+    loaded: Receiver<(PathBuf, Result<image::DynamicImage, failure::Error>)>,
+    next: Option<(usize, image::DynamicImage)>,
+    // Index of the image the worker is decoding, if any.
+    loading: Option<usize>,
+    // Images that failed to load in a row, to give up when none of them do.
+    failures: usize,
+}
+
+impl Slideshow {
+    /// Lists the images of a directory or playlist, to show each for `interval` seconds. Images
+    /// are decoded no larger than `limit`.
+    pub fn open(
+        source: &Path,
+        order: SlideshowOrder,
+        interval: f32,
+        limit: SizeLimit,
+    ) -> Result<Slideshow, failure::Error> {
+        let mut paths = if source.is_dir() {
+            let mut paths = Vec::new();
+
+            for entry in std::fs::read_dir(source)? {
+                let path = entry?.path();
+
+                if path.is_file() && is_image(&path) {
+                    paths.push(path);
+                }
+            }
+
+            paths
+        } else {
+            let directory = source.parent().unwrap_or_else(|| Path::new(""));
+
+            std::fs::read_to_string(source)?
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .map(|line| directory.join(line))
+                .collect()
+        };
+
+        if paths.is_empty() {
+            return Err(Error::NoImages {
+                path: source.to_string_lossy().into_owned(),
+            }
+            .into());
+        }
+
+        match order {
+            SlideshowOrder::Shuffled => paths.shuffle(&mut rand::thread_rng()),
+            SlideshowOrder::Sorted => paths.sort(),
+        }
+
+        let (requests, worker_requests) = mpsc::channel::<PathBuf>();
+        let (worker_loaded, loaded) = mpsc::channel();
+
+        // Stops once the slideshow is dropped and the requests channel with it.
+        thread::spawn(move || {
+            for path in worker_requests {
+                let image = background_loader::open(&path, limit);
+
+                if worker_loaded.send((path, image)).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let mut slideshow = Slideshow {
+            paths,
+            order,
+            current: 0,
+            interval,
+            elapsed: 0.0,
+            requests,
+            loaded,
+            next: None,
+            loading: None,
+            failures: 0,
+        };
+
+        slideshow.preload(1);
+
+        Ok(slideshow)
+    }
+
+    /// The image shown first, which the caller loads itself.
+    pub fn current(&self) -> &Path {
+        &self.paths[self.current]
+    }
+
+    /// Advances the slideshow, returning the next image once it is its turn and it is decoded.
+    pub fn update(&mut self, dt: &Duration) -> Option<image::DynamicImage> {
+        self.elapsed += dt.as_secs_f32();
+
+        match self.loaded.try_recv() {
+            Ok((path, Ok(image))) => {
+                let index = self.loading.take().unwrap();
+
+                self.failures = 0;
+                self.next = Some((index, image));
+
+                debug_assert_eq!(path, self.paths[index]);
+            }
+            Ok((path, Err(e))) => {
+                let index = self.loading.take().unwrap();
+
+                debug_assert_eq!(path, self.paths[index]);
+
+                println!("{}", e);
+
+                self.failures += 1;
+
+                if self.failures < self.paths.len() {
+                    self.preload(index + 1);
+                }
+            }
+            Err(TryRecvError::Empty) | Err(TryRecvError::Disconnected) => (),
+        }
+
+        if self.elapsed < self.interval {
+            return None;
+        }
+
+        let (index, image) = self.next.take()?;
+
+        self.current = index;
+        self.elapsed = 0.0;
+
+        self.preload(index + 1);
+
+        Some(image)
+    }
+
+    /// Starts decoding the image at `index`, wrapping around and reshuffling at the end.
+    fn preload(&mut self, index: usize) {
+        // A single image is shown for good.
+        if self.paths.len() < 2 {
+            return;
+        }
+
+        let mut index = index;
+
+        if index >= self.paths.len() {
+            index = 0;
+
+            if self.order == SlideshowOrder::Shuffled {
+                let current = self.paths[self.current].clone();
+
+                self.paths.shuffle(&mut rand::thread_rng());
+
+                self.current = self.paths.iter().position(|p| *p == current).unwrap();
+
+                // Never show the same image twice in a row.
+                if self.current == 0 {
+                    let last = self.paths.len() - 1;
+
+                    self.paths.swap(0, last);
+                    self.current = last;
+                }
+            }
+        }
+
+        if self.requests.send(self.paths[index].clone()).is_ok() {
+            self.loading = Some(index);
+        }
+    }
+}
+
+fn has_extension(path: &Path, extensions: &[&str]) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .map(|extension| extensions.iter().any(|e| extension.eq_ignore_ascii_case(e)))
+        .unwrap_or(false)
+}
@@ -0,0 +1,269 @@
+﻿use crate::binary::*;
+use crate::droplet::Droplet;
+use nalgebra::Vector2;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+const MAGIC: &[u8; 8] = b"RAINSTAT";
+// Bumped whenever the layout changes, such as when droplets stopped storing where they last shed
+// a trail droplet.
+const VERSION: u32 = 2;
+
+#[derive(Debug, Fail)]
+pub enum Error {
+    #[fail(display = "Not a simulation state file")]
+    InvalidFormat,
+    #[fail(display = "Unsupported simulation state version {}", version)]
+    UnsupportedVersion { version: u32 },
+}
+
+/// Everything needed to resume a simulation exactly where it stopped.
+///
+/// The fog mask lives on the GPU and is stored separately, as an image.
+pub struct SimulationState {
+    pub resolution: (u32, u32),
+
+    pub time_accumulator: f64,
+    pub droplets_accumulator: usize,
+
+    pub rng_seed: u64,
+    pub rng_word_pos: u128,
+
+    pub droplets: Vec<Droplet>,
+    pub unused: Vec<usize>,
+}
+
+impl SimulationState {
+    pub fn load(path: &Path) -> Result<SimulationState, failure::Error> {
+        let mut reader = BufReader::new(File::open(path)?);
+
+        Self::read(&mut reader)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), failure::Error> {
+        let mut writer = BufWriter::new(File::create(path)?);
+
+        self.write(&mut writer)?;
+
+        writer.flush()?;
+
+        Ok(())
+    }
+
+    pub fn read<R: Read>(reader: &mut R) -> Result<SimulationState, failure::Error> {
+        let mut magic = [0u8; 8];
+        reader.read_exact(&mut magic)?;
+
+        if &magic != MAGIC {
+            return Err(Error::InvalidFormat.into());
+        }
+
+        let version = read_u32(reader)?;
+
+        if version != VERSION {
+            return Err(Error::UnsupportedVersion { version }.into());
+        }
+
+        let resolution = (read_u32(reader)?, read_u32(reader)?);
+
+        let time_accumulator = read_f64(reader)?;
+        let droplets_accumulator = read_u64(reader)? as usize;
+
+        let rng_seed = read_u64(reader)?;
+        let rng_word_pos = read_u128(reader)?;
+
+        let droplet_count = read_u64(reader)? as usize;
+        let droplets = (0..droplet_count)
+            .map(|_| read_droplet(reader))
+            .collect::<io::Result<Vec<_>>>()?;
+
+        let unused_count = read_u64(reader)? as usize;
+        let unused = (0..unused_count)
+            .map(|_| read_u64(reader).map(|i| i as usize))
+            .collect::<io::Result<Vec<_>>>()?;
+
+        if unused.iter().any(|&i| i >= droplet_count) {
+            return Err(Error::InvalidFormat.into());
+        }
+
+        Ok(SimulationState {
+            resolution,
+            time_accumulator,
+            droplets_accumulator,
+            rng_seed,
+            rng_word_pos,
+            droplets,
+            unused,
+        })
+    }
+
+    pub fn write<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(MAGIC)?;
+        write_u32(writer, VERSION)?;
+
+        write_u32(writer, self.resolution.0)?;
+        write_u32(writer, self.resolution.1)?;
+
+        write_f64(writer, self.time_accumulator)?;
+        write_u64(writer, self.droplets_accumulator as u64)?;
+
+        write_u64(writer, self.rng_seed)?;
+        write_u128(writer, self.rng_word_pos)?;
+
+        write_u64(writer, self.droplets.len() as u64)?;
+        for droplet in self.droplets.iter() {
+            write_droplet(writer, droplet)?;
+        }
+
+        write_u64(writer, self.unused.len() as u64)?;
+        for &i in self.unused.iter() {
+            write_u64(writer, i as u64)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn read_droplet<R: Read>(reader: &mut R) -> io::Result<Droplet> {
+    let mut droplet = Droplet::new();
+
+    droplet.pos = Vector2::new(read_f32(reader)?, read_f32(reader)?);
+    droplet.size = read_f32(reader)?;
+    droplet.speed = Vector2::new(read_f32(reader)?, read_f32(reader)?);
+    droplet.seed = read_u32(reader)? as i32;
+    droplet.skipping = read_bool(reader)?;
+    droplet.deleted = read_bool(reader)?;
+    droplet.slowing = read_bool(reader)?;
+    droplet.wobble_amplitude = read_f32(reader)?;
+    droplet.wobble_time = read_f32(reader)?;
+
+    Ok(droplet)
+}
+
+fn write_droplet<W: Write>(writer: &mut W, droplet: &Droplet) -> io::Result<()> {
+    write_f32(writer, droplet.pos.x)?;
+    write_f32(writer, droplet.pos.y)?;
+    write_f32(writer, droplet.size)?;
+    write_f32(writer, droplet.speed.x)?;
+    write_f32(writer, droplet.speed.y)?;
+    write_u32(writer, droplet.seed as u32)?;
+    write_bool(writer, droplet.skipping)?;
+    write_bool(writer, droplet.deleted)?;
+    write_bool(writer, droplet.slowing)?;
+    write_f32(writer, droplet.wobble_amplitude)?;
+    write_f32(writer, droplet.wobble_time)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state() -> SimulationState {
+        let droplets = (0..5)
+            .map(|i| {
+                let mut droplet = Droplet::new();
+
+                droplet.pos = Vector2::new(i as f32 * 10.5, 1080.0 - i as f32);
+                droplet.size = 3.0 + i as f32;
+                droplet.speed = Vector2::new(0.0, -0.25 * i as f32);
+                droplet.seed = -7 * i;
+                droplet.skipping = i == 1;
+                droplet.deleted = i == 2 || i == 4;
+                droplet.slowing = i == 3;
+                droplet.wobble_amplitude = 0.1 * i as f32;
+                droplet.wobble_time = 0.5;
+
+                droplet
+            })
+            .collect();
+
+        SimulationState {
+            resolution: (1920, 1080),
+            time_accumulator: 0.731,
+            droplets_accumulator: 42,
+            rng_seed: 0x0123_4567_89ab_cdef,
+            // Past the 64 bits a u64 would hold.
+            rng_word_pos: (1 << 70) + 12_345,
+            droplets,
+            // Slots are checked out in this order, so it has to survive as it is.
+            unused: vec![4, 2],
+        }
+    }
+
+    fn bytes(state: &SimulationState) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        state.write(&mut bytes).unwrap();
+
+        bytes
+    }
+
+    fn read_error(bytes: &[u8]) -> failure::Error {
+        match SimulationState::read(&mut &bytes[..]) {
+            Ok(_) => panic!("the state was read"),
+            Err(e) => e,
+        }
+    }
+
+    #[test]
+    fn state_round_trips() {
+        let original = state();
+        let read = SimulationState::read(&mut bytes(&original).as_slice()).unwrap();
+
+        assert_eq!(read.resolution, original.resolution);
+        assert_eq!(read.time_accumulator, original.time_accumulator);
+        assert_eq!(read.droplets_accumulator, original.droplets_accumulator);
+        assert_eq!(read.rng_seed, original.rng_seed);
+        assert_eq!(read.rng_word_pos, original.rng_word_pos);
+        assert_eq!(read.unused, original.unused);
+
+        assert_eq!(read.droplets.len(), original.droplets.len());
+
+        for (read, original) in read.droplets.iter().zip(original.droplets.iter()) {
+            assert_eq!(read.pos, original.pos);
+            assert_eq!(read.size, original.size);
+            assert_eq!(read.speed, original.speed);
+            assert_eq!(read.seed, original.seed);
+            assert_eq!(read.skipping, original.skipping);
+            assert_eq!(read.deleted, original.deleted);
+            assert_eq!(read.slowing, original.slowing);
+            assert_eq!(read.wobble_amplitude, original.wobble_amplitude);
+            assert_eq!(read.wobble_time, original.wobble_time);
+        }
+
+        assert_eq!(bytes(&read), bytes(&original));
+    }
+
+    #[test]
+    fn unused_index_out_of_range_is_rejected() {
+        let mut state = state();
+        state.unused.push(state.droplets.len());
+
+        assert!(matches!(
+            read_error(&bytes(&state)).downcast_ref::<Error>(),
+            Some(Error::InvalidFormat)
+        ));
+    }
+
+    #[test]
+    fn other_version_is_rejected() {
+        let mut bytes = bytes(&state());
+        bytes[MAGIC.len()..MAGIC.len() + 4].copy_from_slice(&(VERSION - 1).to_le_bytes());
+
+        assert!(matches!(
+            read_error(&bytes).downcast_ref::<Error>(),
+            Some(Error::UnsupportedVersion { version }) if *version == VERSION - 1
+        ));
+    }
+
+    #[test]
+    fn shipped_state_loads() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("assets/state/rainy.state");
+
+        let state = SimulationState::load(&path).unwrap();
+
+        assert!(state.unused.len() < state.droplets.len());
+    }
+}
@@ -0,0 +1,343 @@
+﻿use crate::droplet::Droplet;
+use crate::droplets::Droplets;
+use crate::observer::DropletObserver;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::time::Duration;
+
+// Length of the windows the statistics are rolled over, in simulated seconds.
+const WINDOW_DURATION: f64 = 5.0;
+
+// Live droplets are bucketed by diameter into bins of this many pixels, the last bin being open.
+const SIZE_BIN_WIDTH: f32 = 2.0;
+const SIZE_BIN_COUNT: usize = 16;
+
+/// The statistics of a single window. Rates are per simulated second.
+pub struct Window {
+    pub start: f64,
+    pub duration: f64,
+    /// Whether the window was still open when the statistics were exported, so it is shorter
+    /// than the others.
+    pub partial: bool,
+
+    pub spawns_per_second: f64,
+    pub merges_per_second: f64,
+    pub sheds_per_second: f64,
+    pub exits_per_second: f64,
+
+    /// Mean speed of the droplets that were moving, in pixels per second.
+    pub mean_moving_speed: f64,
+    /// Mean number of droplets that were moving in any frame.
+    pub mean_moving_count: f64,
+
+    /// Mean fraction of the droplet pool in use.
+    pub pool_occupancy: f64,
+    pub rejected_checkouts: usize,
+
+    /// Live droplets per size bin, at the end of the window.
+    pub size_histogram: [usize; SIZE_BIN_COUNT],
+}
+
+/// Collects droplet statistics over fixed windows of simulated time.
+///
+/// Lifecycle events are counted through `DropletObserver`, while everything that describes the
+/// droplets as a whole is gathered by `sample`, which needs to be called after every update.
+#[derive(Default)]
+pub struct Statistics {
+    windows: Vec<Window>,
+
+    time: f64,
+    window_start: f64,
+
+    spawns: usize,
+    merges: usize,
+    sheds: usize,
+    exits: usize,
+
+    frames: usize,
+    moving_speed_sum: f64,
+    moving_count: usize,
+    occupancy_sum: f64,
+
+    rejected_checkouts: usize,
+    last_rejected_checkouts: usize,
+
+    size_histogram: [usize; SIZE_BIN_COUNT],
+}
+
+impl Statistics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Samples the droplets after an update, rolling over to a new window when the current one
+    /// is complete.
+    pub fn sample(&mut self, dt: &Duration, droplets: &Droplets) {
+        let dt = dt.as_secs_f64();
+
+        if dt <= 0.0 {
+            return;
+        }
+
+        self.time += dt;
+        self.frames += 1;
+
+        let mut size_histogram = [0; SIZE_BIN_COUNT];
+
+        for droplet in droplets.into_iter().filter(|d| !d.deleted) {
+            let bin = ((droplet.size / SIZE_BIN_WIDTH) as usize).min(SIZE_BIN_COUNT - 1);
+
+            size_histogram[bin] += 1;
+
+            // Speeds are stored in pixels per frame.
+            if droplet.speed.x != 0.0 || droplet.speed.y != 0.0 {
+                self.moving_speed_sum += droplet.speed.norm() as f64 / dt;
+                self.moving_count += 1;
+            }
+        }
+
+        self.size_histogram = size_histogram;
+        self.occupancy_sum += droplets.used_count() as f64 / droplets.len().max(1) as f64;
+
+        // The pool is replaced when a saved state is restored, which restarts its counter.
+        let rejected_checkouts = droplets.rejected_checkouts();
+        self.rejected_checkouts += rejected_checkouts
+            .checked_sub(self.last_rejected_checkouts)
+            .unwrap_or(rejected_checkouts);
+        self.last_rejected_checkouts = rejected_checkouts;
+
+        if self.time - self.window_start >= WINDOW_DURATION {
+            self.roll_over();
+        }
+    }
+
+    /// Writes the completed windows to a file, as JSON if its extension is `json` and as CSV
+    /// otherwise. The window still open is written last, marked as partial, and carries on
+    /// collecting.
+    pub fn export(&self, path: &Path) -> Result<(), failure::Error> {
+        let mut writer = BufWriter::new(File::create(path)?);
+
+        let json = matches!(path.extension(), Some(ext) if ext.eq_ignore_ascii_case("json"));
+
+        if json {
+            self.write_json(&mut writer)?;
+        } else {
+            self.write_csv(&mut writer)?;
+        }
+
+        writer.flush()?;
+
+        Ok(())
+    }
+
+    pub fn write_csv<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        write!(
+            writer,
+            "start,duration,spawns_per_second,merges_per_second,sheds_per_second,\
+             exits_per_second,mean_moving_speed,mean_moving_count,pool_occupancy,\
+             rejected_checkouts,partial"
+        )?;
+
+        for bin in 0..SIZE_BIN_COUNT {
+            write!(writer, ",{}", size_bin_name(bin))?;
+        }
+
+        writeln!(writer)?;
+
+        let partial = self.partial_window();
+
+        for window in self.windows.iter().chain(partial.iter()) {
+            write!(
+                writer,
+                "{:.3},{:.3},{:.3},{:.3},{:.3},{:.3},{:.3},{:.3},{:.4},{},{}",
+                window.start,
+                window.duration,
+                window.spawns_per_second,
+                window.merges_per_second,
+                window.sheds_per_second,
+                window.exits_per_second,
+                window.mean_moving_speed,
+                window.mean_moving_count,
+                window.pool_occupancy,
+                window.rejected_checkouts,
+                window.partial,
+            )?;
+
+            for count in window.size_histogram.iter() {
+                write!(writer, ",{}", count)?;
+            }
+
+            writeln!(writer)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn write_json<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writeln!(writer, "{{")?;
+        writeln!(writer, "  \"window_duration\": {},", WINDOW_DURATION)?;
+
+        let bins: Vec<String> = (0..SIZE_BIN_COUNT)
+            .map(|bin| format!("\"{}\"", size_bin_name(bin)))
+            .collect();
+
+        writeln!(writer, "  \"size_bins\": [{}],", bins.join(", "))?;
+        writeln!(writer, "  \"windows\": [")?;
+
+        let partial = self.partial_window();
+        let count = self.windows.len() + partial.iter().count();
+
+        for (i, window) in self.windows.iter().chain(partial.iter()).enumerate() {
+            let histogram: Vec<String> = window
+                .size_histogram
+                .iter()
+                .map(|count| count.to_string())
+                .collect();
+
+            writeln!(writer, "    {{")?;
+            writeln!(writer, "      \"start\": {:.3},", window.start)?;
+            writeln!(writer, "      \"duration\": {:.3},", window.duration)?;
+            writeln!(
+                writer,
+                "      \"spawns_per_second\": {:.3},",
+                window.spawns_per_second
+            )?;
+            writeln!(
+                writer,
+                "      \"merges_per_second\": {:.3},",
+                window.merges_per_second
+            )?;
+            writeln!(
+                writer,
+                "      \"sheds_per_second\": {:.3},",
+                window.sheds_per_second
+            )?;
+            writeln!(
+                writer,
+                "      \"exits_per_second\": {:.3},",
+                window.exits_per_second
+            )?;
+            writeln!(
+                writer,
+                "      \"mean_moving_speed\": {:.3},",
+                window.mean_moving_speed
+            )?;
+            writeln!(
+                writer,
+                "      \"mean_moving_count\": {:.3},",
+                window.mean_moving_count
+            )?;
+            writeln!(
+                writer,
+                "      \"pool_occupancy\": {:.4},",
+                window.pool_occupancy
+            )?;
+            writeln!(
+                writer,
+                "      \"rejected_checkouts\": {},",
+                window.rejected_checkouts
+            )?;
+            writeln!(writer, "      \"partial\": {},", window.partial)?;
+            writeln!(
+                writer,
+                "      \"size_histogram\": [{}]",
+                histogram.join(", ")
+            )?;
+
+            let separator = if i + 1 < count { "," } else { "" };
+
+            writeln!(writer, "    }}{}", separator)?;
+        }
+
+        writeln!(writer, "  ]")?;
+        writeln!(writer, "}}")?;
+
+        Ok(())
+    }
+
+    /// The window still open, if anything was sampled in it.
+    fn partial_window(&self) -> Option<Window> {
+        if self.frames > 0 {
+            Some(self.window(true))
+        } else {
+            None
+        }
+    }
+
+    fn window(&self, partial: bool) -> Window {
+        let duration = self.time - self.window_start;
+        let frames = self.frames.max(1) as f64;
+
+        Window {
+            start: self.window_start,
+            duration,
+            partial,
+
+            spawns_per_second: self.spawns as f64 / duration,
+            merges_per_second: self.merges as f64 / duration,
+            sheds_per_second: self.sheds as f64 / duration,
+            exits_per_second: self.exits as f64 / duration,
+
+            mean_moving_speed: if self.moving_count > 0 {
+                self.moving_speed_sum / self.moving_count as f64
+            } else {
+                0.0
+            },
+            mean_moving_count: self.moving_count as f64 / frames,
+
+            pool_occupancy: self.occupancy_sum / frames,
+            rejected_checkouts: self.rejected_checkouts,
+
+            size_histogram: self.size_histogram,
+        }
+    }
+
+    fn roll_over(&mut self) {
+        let window = self.window(false);
+
+        self.windows.push(window);
+
+        self.window_start = self.time;
+
+        self.spawns = 0;
+        self.merges = 0;
+        self.sheds = 0;
+        self.exits = 0;
+
+        self.frames = 0;
+        self.moving_speed_sum = 0.0;
+        self.moving_count = 0;
+        self.occupancy_sum = 0.0;
+
+        self.rejected_checkouts = 0;
+    }
+}
+
+impl DropletObserver for Statistics {
+    fn on_spawn(&mut self, _droplet: &Droplet) {
+        self.spawns += 1;
+    }
+
+    fn on_merge(&mut self, _kept: &Droplet, _absorbed: &Droplet) {
+        self.merges += 1;
+    }
+
+    fn on_shed(&mut self, _parent_snapshot: &Droplet, _child: &Droplet) {
+        self.sheds += 1;
+    }
+
+    fn on_exit(&mut self, _droplet: &Droplet) {
+        self.exits += 1;
+    }
+}
+
+fn size_bin_name(bin: usize) -> String {
+    let from = bin as f32 * SIZE_BIN_WIDTH;
+
+    if bin + 1 < SIZE_BIN_COUNT {
+        format!("size_{}_{}", from, from + SIZE_BIN_WIDTH)
+    } else {
+        format!("size_{}_up", from)
+    }
+}
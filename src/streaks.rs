@@ -0,0 +1,294 @@
+﻿use crate::droplet::Droplet;
+use crate::render_gl::buffer::{ArrayBuffer, VertexArray};
+use crate::vertex::StreakVertex;
+use nalgebra::Vector2;
+use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
+use std::collections::{BTreeMap, HashSet};
+use std::time::Duration;
+
+// Minimum distance between the points of a streak, in pixels.
+const SEGMENT_LENGTH: f32 = 4.0;
+
+// Width of a streak relative to the droplet laying it.
+const WIDTH_FACTOR: f32 = 0.3;
+
+// Seconds a streak stays whole after its droplet stopped or disappeared, before it beads up.
+const BEAD_DELAY_RANGE: (f32, f32) = (4.0, 12.0);
+// Distance between the beads a streak breaks into, in pixels.
+const BEAD_SPACING_RANGE: (f32, f32) = (12.0, 40.0);
+const MIN_BEAD_SIZE: f32 = 1.0;
+
+/// A thin wet streak left behind by a running droplet.
+struct Streak {
+    points: Vec<Vector2<f32>>,
+    width: f32,
+    // Volume deposited by the droplet, in the same units as a droplet's cubed radius.
+    volume: f32,
+    // The droplet that laid the streak, as it was when it last extended it.
+    parent: Droplet,
+    age: f32,
+    bead_delay: f32,
+}
+
+impl Streak {
+    fn length(&self) -> f32 {
+        self.points.windows(2).map(|w| (w[1] - w[0]).norm()).sum()
+    }
+}
+
+/// A droplet a streak broke into.
+pub struct Bead {
+    // A copy of the droplet that laid the streak, as it was when it last extended it.
+    pub parent_snapshot: Droplet,
+    pub pos: Vector2<f32>,
+    pub size: f32,
+}
+
+/// The streaks behind running droplets, which later bead up into regular droplets.
+///
+/// Streaks are keyed by the index of the droplet laying them while it keeps moving, and ordered
+/// so that they bead up deterministically.
+pub struct Streaks {
+    active: BTreeMap<usize, Streak>,
+    detached: Vec<Streak>,
+
+    vbo: ArrayBuffer,
+    vao: VertexArray,
+    vertex_count: usize,
+}
+
+impl Streaks {
+    pub fn new(gl: &gl::Gl) -> Streaks {
+        let vbo = ArrayBuffer::new(gl);
+        let vao = VertexArray::new(gl);
+
+        vao.bind();
+        vbo.bind();
+        StreakVertex::vertex_attrib_pointers(gl);
+        vao.unbind();
+        vbo.unbind();
+
+        Streaks {
+            active: BTreeMap::new(),
+            detached: Vec::new(),
+            vbo,
+            vao,
+            vertex_count: 0,
+        }
+    }
+
+    /// Extends the streak behind a running droplet by the volume it lost this update.
+    ///
+    /// A droplet that jumped further than it could have moved is a new droplet reusing the slot,
+    /// so the old streak is detached and a new one started.
+    pub fn extend(&mut self, rng: &mut ChaCha8Rng, index: usize, droplet: &Droplet, volume: f32) {
+        let jumped = match self.active.get(&index) {
+            Some(streak) => {
+                let last = streak.points[streak.points.len() - 1];
+
+                (droplet.pos - last).norm() > SEGMENT_LENGTH + droplet.speed.norm() + droplet.size
+            }
+            None => false,
+        };
+
+        if jumped {
+            self.detach(rng, index);
+        }
+
+        let streak = self.active.entry(index).or_insert_with(|| Streak {
+            points: vec![droplet.pos, droplet.pos],
+            width: 0.0,
+            volume: 0.0,
+            parent: droplet.clone(),
+            age: 0.0,
+            bead_delay: 0.0,
+        });
+
+        // The last point follows the droplet, the others stay where it left them.
+        let last = streak.points.len() - 1;
+
+        if (streak.points[last - 1] - droplet.pos).norm() >= SEGMENT_LENGTH {
+            streak.points.push(droplet.pos);
+        } else {
+            streak.points[last] = droplet.pos;
+        }
+
+        streak.width = streak.width.max(droplet.size * WIDTH_FACTOR);
+        streak.volume += volume;
+        streak.parent = droplet.clone();
+    }
+
+    /// Detaches the streaks of droplets that were not extended this update.
+    pub fn detach_idle(&mut self, rng: &mut ChaCha8Rng, extended: &HashSet<usize>) {
+        let idle: Vec<usize> = self
+            .active
+            .keys()
+            .filter(|index| !extended.contains(index))
+            .copied()
+            .collect();
+
+        for index in idle {
+            self.detach(rng, index);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.active.clear();
+        self.detached.clear();
+    }
+
+    /// Ages the detached streaks and breaks those whose time came into beads.
+    pub fn update(&mut self, rng: &mut ChaCha8Rng, dt: &Duration) -> Vec<Bead> {
+        let mut beads = Vec::new();
+
+        for streak in self.detached.iter_mut() {
+            streak.age += dt.as_secs_f32();
+        }
+
+        let (beading, detached) = self
+            .detached
+            .drain(..)
+            .partition(|streak| streak.age >= streak.bead_delay);
+
+        self.detached = detached;
+
+        for streak in beading {
+            Self::bead(rng, &streak, &mut beads);
+        }
+
+        beads
+    }
+
+    /// Rebuilds the streak geometry.
+    pub fn upload(&mut self) {
+        let mut vertices = Vec::new();
+
+        for streak in self.active.values() {
+            Self::triangulate(streak, 1.0, &mut vertices);
+        }
+
+        // Detached streaks thin out as they draw their water into beads.
+        for streak in self.detached.iter() {
+            Self::triangulate(
+                streak,
+                1.0 - 0.5 * streak.age / streak.bead_delay,
+                &mut vertices,
+            );
+        }
+
+        self.vbo.bind();
+        self.vbo.dynamic_draw_data(&vertices);
+        self.vbo.unbind();
+
+        self.vertex_count = vertices.len();
+    }
+
+    pub fn render(&self, gl: &gl::Gl) {
+        if self.vertex_count == 0 {
+            return;
+        }
+
+        self.vao.bind();
+
+        unsafe {
+            gl.DrawArrays(gl::TRIANGLES, 0, self.vertex_count as i32);
+        }
+
+        self.vao.unbind();
+    }
+
+    fn detach(&mut self, rng: &mut ChaCha8Rng, index: usize) {
+        if let Some(mut streak) = self.active.remove(&index) {
+            streak.bead_delay = rng.gen_range(BEAD_DELAY_RANGE.0, BEAD_DELAY_RANGE.1);
+
+            self.detached.push(streak);
+        }
+    }
+
+    fn bead(rng: &mut ChaCha8Rng, streak: &Streak, beads: &mut Vec<Bead>) {
+        let length = streak.length();
+
+        let min_volume = (MIN_BEAD_SIZE * 0.5).powf(3.0);
+        let spacing = rng.gen_range(BEAD_SPACING_RANGE.0, BEAD_SPACING_RANGE.1);
+
+        // Too little water for beads just dries up.
+        let count = ((length / spacing).round() as usize)
+            .max(1)
+            .min((streak.volume / min_volume) as usize);
+
+        if count == 0 {
+            return;
+        }
+
+        let size = (streak.volume / count as f32).cbrt() * 2.0;
+
+        // The beads are spread evenly, each in the middle of its share of the streak.
+        let spacing = length / count as f32;
+
+        let mut distance = spacing * 0.5;
+        let mut travelled = 0.0;
+        let mut placed = 0;
+
+        for w in streak.points.windows(2) {
+            let segment = w[1] - w[0];
+            let segment_length = segment.norm();
+
+            while placed < count && distance <= travelled + segment_length {
+                let t = if segment_length > 0.0 {
+                    (distance - travelled) / segment_length
+                } else {
+                    0.0
+                };
+
+                beads.push(Bead {
+                    parent_snapshot: streak.parent.clone(),
+                    pos: w[0] + segment * t + Vector2::new(rng.gen_range(-1.0, 1.0), 0.0),
+                    size,
+                });
+
+                distance += spacing;
+                placed += 1;
+            }
+
+            travelled += segment_length;
+        }
+    }
+
+    fn triangulate(streak: &Streak, thickness: f32, vertices: &mut Vec<StreakVertex>) {
+        let points = &streak.points;
+        let last = points.len() - 1;
+
+        let normal = |i: usize| {
+            let tangent = points[(i + 1).min(last)] - points[i.saturating_sub(1)];
+
+            if tangent.norm_squared() > 0.0 {
+                Vector2::new(-tangent.y, tangent.x).normalize()
+            } else {
+                Vector2::new(1.0, 0.0)
+            }
+        };
+
+        // The oldest end of the streak is the thinnest.
+        let width =
+            |i: usize| streak.width * thickness * (0.5 + 0.5 * i as f32 / last as f32) * 0.5;
+
+        for i in 0..last {
+            let (n0, n1) = (normal(i), normal(i + 1));
+            let (w0, w1) = (width(i), width(i + 1));
+
+            let vertex = |point: Vector2<f32>, normal: Vector2<f32>, across: f32| StreakVertex {
+                pos: (point.x, point.y, 0.0).into(),
+                normal: (normal.x, normal.y).into(),
+                uv: (across, thickness).into(),
+            };
+
+            let a = vertex(points[i] - n0 * w0, n0, 0.0);
+            let b = vertex(points[i] + n0 * w0, n0, 1.0);
+            let c = vertex(points[i + 1] - n1 * w1, n1, 0.0);
+            let d = vertex(points[i + 1] + n1 * w1, n1, 1.0);
+
+            vertices.extend_from_slice(&[a, b, c, c, b, d]);
+        }
+    }
+}
@@ -10,3 +10,24 @@ pub struct Vertex {
     #[location = "2"]
     pub uv: f32_f32,
 }
+
+#[derive(VertexAttribPointers, Copy, Clone, Debug)]
+#[repr(C, packed)]
+pub struct DropletInstance {
+    #[location = "3"]
+    pub offset: f32_f32_f32,
+    #[location = "4"]
+    pub wobble: f32_f32,
+}
+
+#[derive(VertexAttribPointers, Copy, Clone, Debug)]
+#[repr(C, packed)]
+pub struct StreakVertex {
+    #[location = "0"]
+    pub pos: f32_f32_f32,
+    #[location = "1"]
+    pub normal: f32_f32,
+    // x: across the streak, y: opacity.
+    #[location = "2"]
+    pub uv: f32_f32,
+}
@@ -0,0 +1,86 @@
+﻿use crate::config::Wettability;
+use nalgebra::Vector2;
+use std::path::Path;
+
+/// How water repellent every part of the glass is, read from a grayscale image stretched over
+/// the screen.
+///
+/// Repellency goes from -1, glass that wets easily and that droplets cling to, over 0, regular
+/// glass, to 1, glass that droplets bead up and roll off.
+pub struct WettingMask {
+    width: usize,
+    height: usize,
+    // From the bottom row up, like the simulation's coordinates.
+    repellency: Vec<f32>,
+    fingerprint: u64,
+}
+
+impl WettingMask {
+    /// Loads a mask whose bright parts have the given wettability, while its black parts are
+    /// regular glass.
+    pub fn load(
+        path: &Path,
+        bright: Wettability,
+        width: u32,
+        height: u32,
+    ) -> Result<WettingMask, failure::Error> {
+        let mut image = image::open(path)?.to_luma8();
+
+        if image.dimensions() != (width, height) {
+            image = image::imageops::resize(
+                &image,
+                width,
+                height,
+                image::imageops::FilterType::Triangle,
+            );
+        }
+
+        let sign = match bright {
+            Wettability::Hydrophobic => 1.0,
+            Wettability::Hydrophilic => -1.0,
+        };
+
+        let image = image::imageops::flip_vertical(&image);
+
+        let repellency = image
+            .pixels()
+            .map(|pixel| sign * pixel.0[0] as f32 / 255.0)
+            .collect::<Vec<f32>>();
+
+        let fingerprint = fingerprint(&repellency);
+
+        Ok(WettingMask {
+            width: width as usize,
+            height: height as usize,
+            repellency,
+            fingerprint,
+        })
+    }
+
+    /// Identifies the mask as loaded, the same across runs and platforms, so a replay can tell
+    /// whether it plays against the mask it was recorded with.
+    pub fn fingerprint(&self) -> u64 {
+        self.fingerprint
+    }
+
+    pub fn repellency(&self, pos: &Vector2<f32>) -> f32 {
+        let x = (pos.x.max(0.0) as usize).min(self.width - 1);
+        let y = (pos.y.max(0.0) as usize).min(self.height - 1);
+
+        self.repellency[y * self.width + x]
+    }
+}
+
+/// FNV-1a over the repellency values, which depend on the image, its wettability and the size it
+/// is stretched to alike.
+fn fingerprint(repellency: &[f32]) -> u64 {
+    let mut hash = 0xcbf2_9ce4_8422_2325;
+
+    for value in repellency {
+        for byte in value.to_bits().to_le_bytes().iter() {
+            hash = (hash ^ *byte as u64).wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+
+    hash
+}